@@ -0,0 +1,179 @@
+//! Compares building an ActionCircle with a single flood fill against the
+//! previous approach of running A* once for every tile within range.
+//!
+//! Run with `cargo bench --no-default-features`.
+
+extern crate emulation;
+
+use std::cmp::{Ordering};
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+
+use emulation::*;
+
+const ITERATIONS: u32 = 20;
+
+#[derive(Debug, PartialEq, Eq)]
+struct Node {
+    estimate: u32,
+    position: Position
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so that the heap pops the cheapest node first.
+        other.estimate.cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An A* search over a NavMap, stepping between neighbours as the map's
+/// topology defines them. ActionCircle no longer needs it, so it only
+/// lives on here for comparison.
+struct AStar {
+    map:  NavMap,
+    path: Vec<Position>
+}
+
+impl AStar {
+    fn new_from_map(map: NavMap) -> Self {
+        AStar {
+            map,
+            path: Vec::new()
+        }
+    }
+
+    /// Searches for a path between the two positions, returning whether
+    /// one was found. The path can be retrieved afterwards with `walk`.
+    fn find(&mut self, from: Position, to: Position) -> bool {
+        self.path.clear();
+
+        if !self.map.is_walkable(to) || index(&self.map, from).is_none() {
+            return false;
+        }
+
+        let area      = self.map.size().area() as usize;
+        let mut costs = vec![u32::MAX; area];
+        let mut links = vec![None; area];
+        let mut open  = BinaryHeap::new();
+
+        costs[index(&self.map, from).unwrap()] = 0;
+        let topology = self.map.topology();
+        open.push(Node { estimate: topology.distance(from, to), position: from });
+
+        while let Some(Node { position, .. }) = open.pop() {
+            if position == to {
+                let mut current = to;
+                while current != from {
+                    self.path.push(current);
+                    current = links[index(&self.map, current).unwrap()].unwrap();
+                }
+
+                self.path.reverse();
+
+                return true;
+            }
+
+            let cost = costs[index(&self.map, position).unwrap()];
+
+            for neighbour in topology.neighbours(position) {
+                if !self.map.is_walkable(neighbour) {
+                    continue;
+                }
+
+                let index = index(&self.map, neighbour).unwrap();
+                if cost + 1 < costs[index] {
+                    costs[index] = cost + 1;
+                    links[index] = Some(position);
+
+                    open.push(Node {
+                        estimate: cost + 1 + topology.distance(neighbour, to),
+                        position: neighbour
+                    });
+                }
+            }
+        }
+
+        false
+    }
+
+    /// The steps of the last path found, excluding the origin.
+    fn walk(&self) -> std::slice::Iter<'_, Position> {
+        self.path.iter()
+    }
+}
+
+fn index(map: &NavMap, position: Position) -> Option<usize> {
+    let size = map.size();
+    if position.x >= 0 && position.x < size.width  as i32 &&
+       position.y >= 0 && position.y < size.height as i32 {
+        Some((position.x + position.y * size.width as i32) as usize)
+    } else {
+        None
+    }
+}
+
+/// A board with walls running down every eighth column, each with a gap
+/// so that paths have to wind around them.
+fn board(size: u32) -> Board {
+    let mut board = Board::new(Dimension::new(size, size));
+    for x in (8..size - 1).step_by(8) {
+        for y in 1..size - 1 {
+            if y % 16 != (x / 8) % 16 {
+                board.set_tile_at(Position::new(x as i32, y as i32), TileKind::Wall);
+            }
+        }
+    }
+
+    board
+}
+
+/// The per-tile A* search that ActionCircle used to perform.
+fn per_tile_astar(origin: Position, range: u32, space: Option<Space>, board: &Board) -> HashMap<Position, u32> {
+    let mut positions = HashMap::new();
+    let mut astar     = AStar::new_from_map(board.navigation_map(space));
+
+    for position in origin.area(range, Metric::Manhattan).filter(|position| *position != origin) {
+        if board.in_bounds(position) && astar.find(origin, position) {
+            positions.insert(position, astar.walk().count() as u32);
+        }
+    }
+
+    positions
+}
+
+fn time<F: FnMut() -> usize>(mut f: F) -> (Duration, usize) {
+    let mut reached = 0;
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        reached = f();
+    }
+
+    (start.elapsed() / ITERATIONS, reached)
+}
+
+fn main() {
+    println!("{:>6} {:>6} {:>14} {:>14} {:>8}", "board", "range", "per-tile A*", "flood fill", "speedup");
+
+    for &(size, range) in &[(32, 4), (64, 8), (128, 16), (256, 32)] {
+        let board  = board(size);
+        let origin = Position::new(size as i32 / 2 - 3, size as i32 / 2 - 3);
+
+        let (astar, _) = time(|| per_tile_astar(origin, range, Some(Space::Ground), &board).len());
+        let (flood, _) = time(|| ActionCircle::new(origin, range, Some(Space::Ground), &board).into_iter().count());
+
+        println!(
+            "{:>6} {:>6} {:>14?} {:>14?} {:>7.1}x",
+            size,
+            range,
+            astar,
+            flood,
+            astar.as_secs_f64() / flood.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use std::fmt::{Debug};
+
+use crate::{EntityIndex, Game, Position, UnitKind};
+use crate::game::*;
+use crate::transport::*;
+use crate::healing::*;
+
+/// Identifies one of the orders a unit can be given. The abilities of a
+/// kind follow from its definition; see `UnitDefinition::abilities` and
+/// `Definitions::abilities`.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum AbilityKind {
+    Move,
+    Attack,
+    Build,
+    Produce,
+    Embark,
+    Disembark,
+    Heal,
+    Detonate
+}
+
+impl AbilityKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AbilityKind::Move      => "Move",
+            AbilityKind::Attack    => "Attack",
+            AbilityKind::Build     => "Build",
+            AbilityKind::Produce   => "Produce",
+            AbilityKind::Embark    => "Embark",
+            AbilityKind::Disembark => "Disembark",
+            AbilityKind::Heal      => "Heal",
+            AbilityKind::Detonate  => "Detonate"
+        }
+    }
+
+    /// The key that selects the ability in the controller.
+    pub fn hotkey(&self) -> char {
+        match self {
+            AbilityKind::Move      => 'M',
+            AbilityKind::Attack    => 'A',
+            AbilityKind::Build     => 'B',
+            AbilityKind::Produce   => 'P',
+            AbilityKind::Embark    => 'E',
+            AbilityKind::Disembark => 'D',
+            AbilityKind::Heal      => 'H',
+            AbilityKind::Detonate  => 'X'
+        }
+    }
+
+    /// The tiles the ability is aimed at.
+    pub fn targeting(&self) -> Targeting {
+        match self {
+            AbilityKind::Move      => Targeting::Movement,
+            AbilityKind::Attack    => Targeting::Range,
+            AbilityKind::Detonate  => Targeting::Own,
+            _                      => Targeting::Adjacent
+        }
+    }
+
+    /// Looks up the implementation of the ability.
+    pub fn ability(&self) -> &'static dyn Ability {
+        match self {
+            AbilityKind::Move      => &MoveAbility,
+            AbilityKind::Attack    => &AttackAbility,
+            AbilityKind::Build     => &BuildAbility,
+            AbilityKind::Produce   => &ProduceAbility,
+            AbilityKind::Embark    => &EmbarkAbility,
+            AbilityKind::Disembark => &DisembarkAbility,
+            AbilityKind::Heal      => &HealAbility,
+            AbilityKind::Detonate  => &DetonateAbility
+        }
+    }
+}
+
+/// The tiles an ability can be aimed at, before its validator decides
+/// which of them are allowed.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Targeting {
+    /// The unit's own tile.
+    Own,
+
+    /// The tiles next to the unit.
+    Adjacent,
+
+    /// The tiles within the unit's attack range.
+    Range,
+
+    /// The tiles the unit can reach this turn.
+    Movement
+}
+
+impl Targeting {
+    pub fn tiles(&self, game: &Game, entity: EntityIndex) -> Vec<Position> {
+        let unit = match game.units.get(entity) {
+            Some(unit) => unit,
+            None       => return Vec::new()
+        };
+
+        match self {
+            Targeting::Own      => vec![unit.position],
+            Targeting::Adjacent => game.board
+                .neighbours(unit.position)
+                .filter(|position| game.board.in_bounds(*position))
+                .collect(),
+            Targeting::Range    => game.attack_range(entity).unwrap_or_default(),
+            Targeting::Movement => game.movement_range(entity)
+                .map(|action_circle| action_circle.into_iter().map(|(position, _)| position).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// An order a unit can be given. The controller, the computer players and
+/// the rules all go through the same abilities, so a unit is offered
+/// exactly the orders it is allowed to carry out.
+pub trait Ability: Debug {
+    fn kind(&self) -> AbilityKind;
+
+    /// The fewest actions the unit needs to use the ability.
+    fn cost(&self) -> u32 {
+        1
+    }
+
+    /// The options to pick from before the ability is aimed, such as the
+    /// structure to build. Most abilities have none.
+    fn choices(&self, _game: &Game, _entity: EntityIndex) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The intent for using the ability on the tile with the chosen
+    /// option, or None if there is nothing there to use it on.
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, choice: usize) -> Option<Intent>;
+
+    /// Whether the unit has the ability and the actions to use it.
+    fn is_usable(&self, game: &Game, entity: EntityIndex) -> bool {
+        game.units
+            .get(entity)
+            .is_some_and(|unit| game.definitions.abilities(&unit.kind).contains(&self.kind()) && unit.actions >= self.cost())
+    }
+
+    /// Whether using the ability on the tile with the chosen option would
+    /// be allowed by the rules.
+    fn validate(&self, game: &Game, entity: EntityIndex, at: Position, choice: usize) -> bool {
+        self.intent(game, entity, at, choice)
+            .is_some_and(|intent| validate_intent(game, &intent).is_ok())
+    }
+
+    /// The tiles the ability can be used on with any of its options.
+    fn targets(&self, game: &Game, entity: EntityIndex) -> Vec<Position> {
+        if !self.is_usable(game, entity) {
+            return Vec::new();
+        }
+
+        let choices = self.choices(game, entity).len().max(1);
+
+        self.kind().targeting()
+            .tiles(game, entity)
+            .into_iter()
+            .filter(|at| (0..choices).any(|choice| self.validate(game, entity, *at, choice)))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct MoveAbility;
+
+impl Ability for MoveAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Move
+    }
+
+    fn intent(&self, _game: &Game, entity: EntityIndex, at: Position, _choice: usize) -> Option<Intent> {
+        Some(Intent::Move(IntentToMove {
+            entity,
+            to:   at,
+            path: None
+        }))
+    }
+
+    // Every tile in the movement range is reachable, so only the occupied
+    // ones need to be left out.
+    fn targets(&self, game: &Game, entity: EntityIndex) -> Vec<Position> {
+        if !self.is_usable(game, entity) {
+            return Vec::new();
+        }
+
+        self.kind().targeting()
+            .tiles(game, entity)
+            .into_iter()
+            .filter(|position| game.board.entity_at(*position).is_none())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct AttackAbility;
+
+impl Ability for AttackAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Attack
+    }
+
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, _choice: usize) -> Option<Intent> {
+        Some(Intent::Attack(IntentToAttack {
+            entity,
+            target_entity: game.board.entity_at(at)?
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildAbility;
+
+impl BuildAbility {
+    fn structures(game: &Game) -> impl Iterator<Item = UnitKind> + '_ {
+        game.definitions
+            .all()
+            .filter(|definition| definition.build_cost().is_some())
+            .map(|definition| definition.kind.clone())
+    }
+}
+
+impl Ability for BuildAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Build
+    }
+
+    fn choices(&self, game: &Game, _entity: EntityIndex) -> Vec<String> {
+        BuildAbility::structures(game)
+            .map(|kind| format!("{:?}", kind))
+            .collect()
+    }
+
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, choice: usize) -> Option<Intent> {
+        Some(Intent::Build(IntentToBuild {
+            entity,
+            kind: BuildAbility::structures(game).nth(choice)?,
+            at
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct ProduceAbility;
+
+impl Ability for ProduceAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Produce
+    }
+
+    fn choices(&self, game: &Game, entity: EntityIndex) -> Vec<String> {
+        game.units
+            .get(entity)
+            .and_then(|unit| game.definitions.get(&unit.kind))
+            .map(|definition| definition.roster.iter().map(|kind| format!("{:?}", kind)).collect())
+            .unwrap_or_default()
+    }
+
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, choice: usize) -> Option<Intent> {
+        Some(Intent::Produce(IntentToProduce {
+            entity,
+            kind: game.definitions.get(&game.units.get(entity)?.kind)?.roster.get(choice)?.clone(),
+            at
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct EmbarkAbility;
+
+impl Ability for EmbarkAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Embark
+    }
+
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, _choice: usize) -> Option<Intent> {
+        Some(Intent::Embark(IntentToEmbark {
+            entity,
+            transport: game.board.entity_at(at)?
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct DisembarkAbility;
+
+impl Ability for DisembarkAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Disembark
+    }
+
+    fn choices(&self, game: &Game, entity: EntityIndex) -> Vec<String> {
+        game.units
+            .get(entity)
+            .map(|unit| unit.cargo.iter().map(|cargo| format!("{} ({}/{})", cargo.name, cargo.health, cargo.health_max)).collect())
+            .unwrap_or_default()
+    }
+
+    fn intent(&self, _game: &Game, entity: EntityIndex, at: Position, choice: usize) -> Option<Intent> {
+        Some(Intent::Disembark(IntentToDisembark {
+            entity,
+            cargo: choice,
+            at
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct HealAbility;
+
+impl Ability for HealAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Heal
+    }
+
+    fn intent(&self, game: &Game, entity: EntityIndex, at: Position, _choice: usize) -> Option<Intent> {
+        Some(Intent::Heal(IntentToHeal {
+            entity,
+            patient_entity: game.board.entity_at(at)?
+        }))
+    }
+}
+
+#[derive(Debug)]
+pub struct DetonateAbility;
+
+impl Ability for DetonateAbility {
+    fn kind(&self) -> AbilityKind {
+        AbilityKind::Detonate
+    }
+
+    fn intent(&self, _game: &Game, entity: EntityIndex, _at: Position, _choice: usize) -> Option<Intent> {
+        Some(Intent::Detonate(IntentToDetonate {
+            entity
+        }))
+    }
+}
+
+/// Checks an intent against the rules without applying it.
+pub fn validate_intent(game: &Game, intent: &Intent) -> Result<(), IntentError> {
+    validate_owner(game, intent)?;
+    validate_ability(game, intent)?;
+
+    match intent {
+        Intent::Move(intent)      => validate_move(game, intent).map(|_| ()).map_err(IntentError::Move),
+        Intent::Attack(intent)    => validate_attack(game, intent).map_err(IntentError::Attack),
+        Intent::Build(intent)     => validate_build(game, intent).map_err(IntentError::Build),
+        Intent::Produce(intent)   => validate_produce(game, intent).map_err(IntentError::Produce),
+        Intent::Embark(intent)    => validate_embark(game, intent).map_err(IntentError::Embark),
+        Intent::Disembark(intent) => validate_disembark(game, intent).map_err(IntentError::Disembark),
+        Intent::Heal(intent)      => validate_heal(game, intent).map_err(IntentError::Heal),
+        Intent::Detonate(intent)  => validate_detonate(game, intent).map_err(IntentError::Detonate),
+        Intent::EndTurn           => Ok(())
+    }
+}
+
+/// Checks that the unit giving the order belongs to the team whose turn it
+/// is.
+pub fn validate_owner(game: &Game, intent: &Intent) -> Result<(), IntentError> {
+    match intent.entity().and_then(|entity| game.units.get(entity)) {
+        Some(unit) if unit.team != game.player => Err(IntentError::NotYourUnit),
+        _                                      => Ok(())
+    }
+}
+
+/// Checks that the unit giving the order has the ability it needs.
+pub fn validate_ability(game: &Game, intent: &Intent) -> Result<(), IntentError> {
+    let (entity, ability) = match (intent.entity(), intent.ability()) {
+        (Some(entity), Some(ability)) => (entity, ability),
+        _                             => return Ok(())
+    };
+
+    match game.units.get(entity) {
+        Some(unit) if !game.definitions.abilities(&unit.kind).contains(&ability) => Err(IntentError::AbilityUnavailable(ability)),
+        _                                                                       => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Team, SpawnData};
+
+    #[test]
+    fn abilities_follow_from_the_definitions() {
+        let game = Game::new(Board::new(Dimension::new(5, 5)));
+        let abilities = |kind| game.definitions.abilities(&kind);
+
+        assert_eq!(abilities(UnitKind::TANK), vec![AbilityKind::Move, AbilityKind::Attack]);
+        assert_eq!(abilities(UnitKind::MEDIC), vec![AbilityKind::Move, AbilityKind::Heal, AbilityKind::Embark]);
+        assert_eq!(abilities(UnitKind::BARRACKS), vec![AbilityKind::Disembark, AbilityKind::Produce]);
+        assert!(abilities(UnitKind::MISSILE).contains(&AbilityKind::Detonate));
+        assert!(abilities(UnitKind::UNKNOWN).is_empty());
+    }
+
+    #[test]
+    fn units_cannot_use_abilities_they_lack() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK, Team::Red, Position::new(2, 2))).unwrap();
+
+        let build = Intent::Build(IntentToBuild { entity: tank, kind: UnitKind::BARRACKS, at: Position::new(2, 3) });
+        assert!(matches!(validate_ability(&game, &build), Err(IntentError::AbilityUnavailable(AbilityKind::Build))));
+        assert!(!AbilityKind::Build.ability().is_usable(&game, tank));
+        assert!(AbilityKind::Move.ability().is_usable(&game, tank));
+    }
+}
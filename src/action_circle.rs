@@ -1,40 +1,94 @@
-use tcod::pathfinding::{AStar};
 use crate::{Board, Position, Space};
-use std::collections::{HashMap};
+use std::collections::{HashMap, VecDeque};
+
+/// How a unit standing on a tile affects movement through it.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Occupancy {
+    /// Nothing is in the way.
+    Free,
+
+    /// The tile can be crossed but not ended on.
+    PassThrough,
+
+    /// The tile cannot be entered.
+    Blocked
+}
 
 pub struct ActionCircle {
-    positions: HashMap<Position, u32>
+    origin:    Position,
+    positions: HashMap<Position, u32>,
+    links:     HashMap<Position, Position>
 }
 
 /// An ActionCircle is a collection of positions that represent an area
 /// that a unit or building can interact with.
 impl ActionCircle {
+    /// Floods outwards from the origin, recording the cost of every
+    /// position that can be reached within range and the step it was
+    /// reached from. Every step costs one action, so positions are visited
+    /// in order of cost and the first visit is always the cheapest.
     pub fn new(origin: Position, range: u32, space: Option<Space>, board: &Board) -> Self {
-        let positions = {
-            let mut positions = HashMap::new();
-
-            let mut astar = {
-                let map = board.navigation_map(space);
-                AStar::new_from_map(map, 0.0)
-            };
-
-            let radius = origin.radius(range as i32);
-            for position in radius {
-                if board.in_bounds(position) &&
-                   astar.find(origin.into(), position.into()) {
-
-                    positions.insert(
-                        position,
-                        astar.walk().count() as u32
-                    );
-                }
+        ActionCircle::with_occupancy(origin, range, space, board, |_| Occupancy::Free)
+    }
+
+    /// As `new`, but units on the board may block the flood or be crossed
+    /// without the tiles they stand on becoming part of the circle.
+    pub fn with_occupancy<F>(origin: Position, range: u32, space: Option<Space>, board: &Board, occupancy: F) -> Self
+        where F: Fn(Position) -> Occupancy {
+        let mut positions = HashMap::new();
+        let mut links     = HashMap::new();
+        let mut frontier  = VecDeque::new();
+
+        let can_traverse = |position: Position| {
+            if !board.in_bounds(position) {
+                return false;
             }
 
-            positions
+            let tile = board.tile_at(position).unwrap();
+            if let Some(space) = space {
+                space.can_traverse(tile.traverse())
+            } else {
+                !tile.is_wall()
+            }
         };
-        
+
+        frontier.push_back((origin, 0));
+
+        while let Some((position, cost)) = frontier.pop_front() {
+            if cost == range {
+                continue;
+            }
+
+            for neighbour in board.neighbours(position) {
+                if neighbour == origin ||
+                   links.contains_key(&neighbour) ||
+                   !can_traverse(neighbour) {
+                    continue;
+                }
+
+                match occupancy(neighbour) {
+                    Occupancy::Free => {
+                        positions.insert(neighbour, cost + 1);
+                    },
+
+                    Occupancy::PassThrough => {
+
+                    },
+
+                    Occupancy::Blocked => {
+                        continue;
+                    }
+                }
+
+                links.insert(neighbour, position);
+                frontier.push_back((neighbour, cost + 1));
+            }
+        }
+
         ActionCircle {
-            positions
+            origin,
+            positions,
+            links
         }
     }
 
@@ -45,10 +99,31 @@ impl ActionCircle {
 
     /// The number of actions required to reach the position.
     pub fn cost_to(&self, position: Position) -> Option<u32> {
-        match self.positions.get(&position) {
-            Some(cost) => Some(*cost),
-            None       => None
+        self.positions.get(&position).copied()
+    }
+
+    /// The steps taken to reach the position, excluding the origin. Tiles
+    /// that can only be passed through have a path even though they are
+    /// not part of the circle.
+    pub fn path_to(&self, position: Position) -> Option<Vec<Position>> {
+        if !self.links.contains_key(&position) {
+            return None;
+        }
+
+        let mut path    = vec![position];
+        let mut current = position;
+        while let Some(previous) = self.links.get(&current) {
+            if *previous == self.origin {
+                break;
+            }
+
+            path.push(*previous);
+            current = *previous;
         }
+
+        path.reverse();
+
+        Some(path)
     }
 }
 
@@ -65,4 +140,58 @@ impl IntoIterator for ActionCircle {
 
         result.into_iter()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dimension, TileKind};
+
+    #[test]
+    fn costs_count_the_steps_from_the_origin() {
+        let board  = Board::new(Dimension::new(7, 7));
+        let circle = ActionCircle::new(Position::new(3, 3), 2, Some(Space::Ground), &board);
+
+        assert!(!circle.contains(Position::new(3, 3)));
+        assert_eq!(circle.cost_to(Position::new(4, 3)), Some(1));
+        assert_eq!(circle.cost_to(Position::new(4, 4)), Some(2));
+        assert_eq!(circle.cost_to(Position::new(5, 4)), None);
+        assert_eq!(circle.into_iter().count(), 12);
+    }
+
+    #[test]
+    fn walls_and_water_are_walked_around() {
+        let mut board = Board::new(Dimension::new(7, 3));
+        board.set_tile_at(Position::new(2, 1), TileKind::Wall);
+        board.set_tile_at(Position::new(4, 1), TileKind::Ocean);
+
+        let circle = ActionCircle::new(Position::new(1, 1), 4, Some(Space::Ground), &board);
+
+        assert!(!circle.contains(Position::new(2, 1)));
+        assert!(!circle.contains(Position::new(4, 1)));
+        assert_eq!(circle.cost_to(Position::new(3, 1)), None);
+
+        let boat = ActionCircle::new(Position::new(4, 1), 4, Some(Space::Water), &board);
+        assert_eq!(boat.into_iter().count(), 0);
+    }
+
+    #[test]
+    fn paths_pass_through_but_do_not_end_on_friendlies() {
+        let board    = Board::new(Dimension::new(7, 3));
+        let friendly = Position::new(2, 1);
+        let enemy    = Position::new(4, 1);
+
+        let circle = ActionCircle::with_occupancy(Position::new(1, 1), 3, Some(Space::Ground), &board, |position| {
+            match position {
+                position if position == friendly => Occupancy::PassThrough,
+                position if position == enemy    => Occupancy::Blocked,
+                _                                => Occupancy::Free
+            }
+        });
+
+        assert!(!circle.contains(friendly));
+        assert!(!circle.contains(enemy));
+        assert_eq!(circle.path_to(Position::new(3, 1)), Some(vec![friendly, Position::new(3, 1)]));
+        assert_eq!(circle.path_to(Position::new(5, 1)), None);
+    }
+}
@@ -0,0 +1,351 @@
+use std::collections::{HashSet};
+use std::fmt::{Debug};
+use std::str::{FromStr};
+
+use crate::{EntityIndex, Game, Position, Team, Unit, Special, AbilityKind, Occupancy};
+use crate::healing::{can_heal};
+use crate::game::*;
+
+/// Who gives the orders for a team.
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum Control {
+    #[default]
+    Human,
+    Computer
+}
+
+impl FromStr for Control {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Human"    => Ok(Control::Human),
+            "Computer" => Ok(Control::Computer),
+            _          => Err(())
+        }
+    }
+}
+
+/// A computer player. The game asks for one intent at a time so that each
+/// decision sees the result of the last one, until the player ends its
+/// turn with `Intent::EndTurn`.
+pub trait Ai: Debug {
+    fn think(&mut self, game: &Game) -> Intent;
+}
+
+/// Sends every unit towards the nearest enemy and attacks whatever comes
+/// into range, bringing aircraft home before they run out of fuel.
+/// Engineers and medics tend to wounded neighbours first, and medics
+/// follow the wounded rather than the enemy. Structures produce the
+/// cheapest unit the team can afford.
+///
+/// The computer plays by the same fog of war as everyone else. Units
+/// advance on the enemies their team can see, and when there are none
+/// they scout the tiles the team has not explored yet, then those it
+/// cannot currently see.
+#[derive(Debug, Default)]
+pub struct GreedyAi {
+    turn:     u32,
+    attacked: HashSet<EntityIndex>,
+    moved:    HashSet<EntityIndex>,
+    produced: HashSet<EntityIndex>
+}
+
+impl GreedyAi {
+    pub fn new() -> Self {
+        GreedyAi::default()
+    }
+
+    fn attack(&mut self, game: &Game, entity: EntityIndex, unit: &Unit) -> Option<Intent> {
+        if unit.damage == 0 || self.attacked.contains(&entity) {
+            return None;
+        }
+
+        let ability = AbilityKind::Attack.ability();
+        let target  = ability
+            .targets(game, entity)
+            .into_iter()
+            .min_by_key(|position| game.board.entity_at(*position).map(|target| game.units[target].health))?;
+
+        self.attacked.insert(entity);
+
+        ability.intent(game, entity, target, 0)
+    }
+
+    fn heal(&mut self, game: &Game, entity: EntityIndex) -> Option<Intent> {
+        let ability = AbilityKind::Heal.ability();
+        let patient = ability
+            .targets(game, entity)
+            .into_iter()
+            .min_by_key(|position| game.board.entity_at(*position).map(|patient| game.units[patient].health))?;
+
+        ability.intent(game, entity, patient, 0)
+    }
+
+    /// Sends units that cannot fight towards the nearest wounded unit they
+    /// are able to heal.
+    fn tend(&mut self, game: &Game, entity: EntityIndex, unit: &Unit) -> Option<Intent> {
+        if !game.definitions.abilities(&unit.kind).contains(&AbilityKind::Heal) || unit.damage != 0 || self.moved.contains(&entity) {
+            return None;
+        }
+
+        self.moved.insert(entity);
+
+        let wounded: Vec<Position> = game.units
+            .iter()
+            .filter(|(other_entity, other)| *other_entity != entity && can_heal(unit, other) && other.health < other.health_max)
+            .map(|(_, other)| other.position)
+            .collect();
+
+        approach(game, entity, unit, &wounded)
+    }
+
+    fn advance(&mut self, game: &Game, entity: EntityIndex, unit: &Unit) -> Option<Intent> {
+        if unit.damage == 0 || self.moved.contains(&entity) {
+            return None;
+        }
+
+        self.moved.insert(entity);
+
+        let enemies: Vec<Position> = game.units
+            .iter()
+            .filter(|(_, other)| other.team != unit.team && game.can_see(unit.team, other.position))
+            .map(|(_, other)| other.position)
+            .collect();
+
+        if !enemies.is_empty() {
+            return approach(game, entity, unit, &enemies);
+        }
+
+        let board = Position::new(0, 0).rectangle(game.board.width(), game.board.height());
+        let (unexplored, hidden): (Vec<Position>, Vec<Position>) = board
+            .filter(|position| !game.can_see(unit.team, *position))
+            .partition(|position| !game.has_explored(unit.team, *position));
+
+        if !unexplored.is_empty() {
+            approach(game, entity, unit, &unexplored)
+        } else {
+            approach(game, entity, unit, &hidden)
+        }
+    }
+
+    /// Sends aircraft that are running low on fuel back to the nearest
+    /// Airbase or carrier.
+    fn refuel(&mut self, game: &Game, entity: EntityIndex, unit: &Unit) -> Option<Intent> {
+        if !unit.uses_fuel() || unit.fuel > 2 || self.moved.contains(&entity) {
+            return None;
+        }
+
+        self.moved.insert(entity);
+
+        let tankers: Vec<Position> = game.units
+            .iter()
+            .filter(|(_, other)| other.team == unit.team && other.has_special(Special::Refuel))
+            .map(|(_, other)| other.position)
+            .collect();
+
+        approach(game, entity, unit, &tankers)
+    }
+
+    fn produce(&mut self, game: &Game, entity: EntityIndex, unit: &Unit) -> Option<Intent> {
+        if self.produced.contains(&entity) {
+            return None;
+        }
+
+        self.produced.insert(entity);
+
+        let ability = AbilityKind::Produce.ability();
+        let tiles   = AbilityKind::Produce.targeting().tiles(game, entity);
+
+        // The cheapest unit that can be produced somewhere.
+        let (choice, at) = game.definitions
+            .get(&unit.kind)?
+            .roster
+            .iter()
+            .enumerate()
+            .filter_map(|(choice, kind)| {
+                let cost = game.definitions.get(kind)?.cost;
+                let at   = tiles.iter().copied().find(|at| ability.validate(game, entity, *at, choice))?;
+
+                Some((cost, choice, at))
+            })
+            .min_by_key(|(cost, _, _)| *cost)
+            .map(|(_, choice, at)| (choice, at))?;
+
+        ability.intent(game, entity, at, choice)
+    }
+
+    /// Uses the ability if the unit has a use for it right now.
+    fn use_ability(&mut self, game: &Game, entity: EntityIndex, unit: &Unit, ability: AbilityKind) -> Option<Intent> {
+        match ability {
+            AbilityKind::Heal    => self.heal(game, entity),
+            AbilityKind::Attack  => self.attack(game, entity, unit),
+            AbilityKind::Produce => self.produce(game, entity, unit),
+            AbilityKind::Move    => {
+                self.refuel(game, entity, unit)
+                    .or_else(|| self.tend(game, entity, unit))
+                    .or_else(|| self.advance(game, entity, unit))
+            },
+
+            _ => None
+        }
+    }
+}
+
+/// The order in which the greedy player tries each unit's abilities.
+/// Building, transporting and detonating are left to human players.
+const PRIORITY: [AbilityKind; 4] = [AbilityKind::Heal, AbilityKind::Attack, AbilityKind::Move, AbilityKind::Produce];
+
+/// Moves the unit as close as it can get to the nearest of the targets.
+/// Returns None if it cannot get any closer.
+fn approach(game: &Game, entity: EntityIndex, unit: &Unit, targets: &[Position]) -> Option<Intent> {
+    let distance_to_target = |position: Position| {
+        targets
+            .iter()
+            .map(|target| game.board.distance(position, *target))
+            .min()
+    };
+
+    let current = distance_to_target(unit.position)?;
+
+    let action_circle = game.movement_range(entity)?;
+    let (to, distance) = action_circle
+        .into_iter()
+        .filter(|(position, _)| game.occupancy(unit.team, *position) == Occupancy::Free)
+        .filter_map(|(position, cost)| distance_to_target(position).map(|distance| (position, distance, cost)))
+        .min_by_key(|(position, distance, cost)| (*distance, *cost, position.x, position.y))
+        .map(|(position, distance, _)| (position, distance))?;
+
+    if distance >= current {
+        return None;
+    }
+
+    Some(Intent::Move(IntentToMove {
+        entity,
+        to,
+        path: None
+    }))
+}
+
+impl Ai for GreedyAi {
+    fn think(&mut self, game: &Game) -> Intent {
+        if game.turn != self.turn {
+            self.turn = game.turn;
+            self.attacked.clear();
+            self.moved.clear();
+            self.produced.clear();
+        }
+
+        let team: Team = game.player;
+
+        for (entity, unit) in &game.units {
+            if unit.team != team || unit.actions == 0 {
+                continue;
+            }
+
+            let abilities = game.definitions.abilities(&unit.kind);
+            let intent    = PRIORITY
+                .iter()
+                .filter(|ability| abilities.contains(ability))
+                .find_map(|ability| self.use_ability(game, entity, unit, *ability));
+
+            if let Some(intent) = intent {
+                return intent;
+            }
+        }
+
+        Intent::EndTurn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Metric, SpawnData, UnitKind, apply_intent};
+
+    fn game_for_red(width: u32, height: u32) -> Game {
+        let mut game = Game::new(Board::new(Dimension::new(width, height)));
+        game.player  = Team::Red;
+        game
+    }
+
+    fn spawn(game: &mut Game, kind: UnitKind, team: Team, x: i32, y: i32) -> EntityIndex {
+        game.spawn(SpawnData::new(kind, team, Position::new(x, y))).unwrap()
+    }
+
+    #[test]
+    fn attacks_the_weakest_enemy_in_range() {
+        let mut game = game_for_red(6, 6);
+        let tank     = spawn(&mut game, UnitKind::TANK,     Team::Red,  2, 2);
+        let infantry = spawn(&mut game, UnitKind::INFANTRY, Team::Blue, 3, 2);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 2, 3);
+
+        match GreedyAi::new().think(&game) {
+            Intent::Attack(intent) => {
+                assert_eq!(intent.entity, tank);
+                assert_eq!(intent.target_entity, infantry);
+            },
+
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn advances_on_the_nearest_enemy_once_per_turn() {
+        let mut game = game_for_red(12, 3);
+        let tank     = spawn(&mut game, UnitKind::TANK, Team::Red,  1, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 10, 1);
+
+        let mut ai = GreedyAi::new();
+        let intent = ai.think(&game);
+        match &intent {
+            Intent::Move(intent) => {
+                assert_eq!(intent.entity, tank);
+                assert_eq!(intent.to, Position::new(3, 1));
+            },
+
+            other => panic!("{:?}", other)
+        }
+
+        apply_intent(&mut game, intent).unwrap();
+
+        assert!(matches!(ai.think(&game), Intent::EndTurn));
+    }
+
+    #[test]
+    fn explores_rather_than_chasing_hidden_enemies() {
+        let mut game = game_for_red(16, 3);
+        let tank     = spawn(&mut game, UnitKind::TANK, Team::Red,  8, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 1, 1);
+
+        game.visibility.get_mut(&Team::Red).unwrap().update([(Position::new(2, 1), 4)], Metric::Manhattan);
+        game.update_visibility();
+        assert!(!game.can_see(Team::Red, Position::new(1, 1)));
+
+        match GreedyAi::new().think(&game) {
+            Intent::Move(intent) => {
+                assert_eq!(intent.entity, tank);
+                assert!(intent.to.x > 8);
+            },
+
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn structures_produce_the_cheapest_affordable_unit() {
+        let mut game = game_for_red(5, 5);
+        let factory  = spawn(&mut game, UnitKind::FACTORY, Team::Red, 2, 2);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 1, 1);
+        game.funds.insert(Team::Red, 300);
+
+        match GreedyAi::new().think(&game) {
+            Intent::Produce(intent) => {
+                assert_eq!(intent.entity, factory);
+                assert_eq!(intent.kind, UnitKind::HUMVEE);
+            },
+
+            other => panic!("{:?}", other)
+        }
+    }
+}
@@ -1,11 +1,11 @@
-use tcod::colors::*;
-use tcod::{Map as NavMap};
-use tcod::pathfinding::{AStar};
+use std::str::{FromStr};
 
 use generational_arena::{Index as EntityIndex};
 
 use crate::entity::{Space};
+use crate::navigation::{NavMap};
 use crate::position::*;
+use crate::geometry::{Topology};
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub struct Dimension {
@@ -37,53 +37,96 @@ pub enum Traverse {
 pub enum TileKind {
     Floor,
     Wall,
-    Ocean
+    Ocean,
+    Forest
+}
+
+impl TileKind {
+    /// The character used for the tile in save and map files.
+    pub fn symbol(&self) -> char {
+        match self {
+            TileKind::Floor  => '.',
+            TileKind::Wall   => '#',
+            TileKind::Ocean  => '~',
+            TileKind::Forest => '"'
+        }
+    }
+
+    /// How much the tile reduces the damage dealt to a unit standing on it.
+    pub fn defense(&self) -> u32 {
+        match self {
+            TileKind::Forest => 1,
+            _                => 0
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        match symbol {
+            '.' => Some(TileKind::Floor),
+            '#' => Some(TileKind::Wall),
+            '~' => Some(TileKind::Ocean),
+            '"' => Some(TileKind::Forest),
+            _   => None
+        }
+    }
+}
+
+impl FromStr for TileKind {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Floor"  => Ok(TileKind::Floor),
+            "Wall"   => Ok(TileKind::Wall),
+            "Ocean"  => Ok(TileKind::Ocean),
+            "Forest" => Ok(TileKind::Forest),
+            _        => Err(())
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Tile {
-    traverse:   Traverse,
-    fore_color: Color,
-    back_color: Color,
-    glyph:      char
+    kind:     TileKind,
+    traverse: Traverse,
+    glyph:    char
 }
 
 impl Tile {
     pub fn new(kind: TileKind) -> Self {
         match kind {
             TileKind::Floor => Tile {
-                traverse:   Traverse::Ground,
-                fore_color: DARK_GREY,
-                back_color: BLACK,
-                glyph:      '.'
+                kind,
+                traverse: Traverse::Ground,
+                glyph:    '.'
             },
 
             TileKind::Wall => Tile {
-                traverse:   Traverse::Wall,
-                fore_color: DARK_GREY,
-                back_color: DARK_GREY,
-                glyph:      ' '
+                kind,
+                traverse: Traverse::Wall,
+                glyph:    ' '
             },
 
             TileKind::Ocean => Tile {
-                traverse:   Traverse::Water,
-                fore_color: DARKER_BLUE,
-                back_color: DARKEST_BLUE,
-                glyph:      '~'
+                kind,
+                traverse: Traverse::Water,
+                glyph:    '~'
             },
-        }
-    }
 
-    pub fn traverse(&self) -> Traverse {
-        self.traverse
+            TileKind::Forest => Tile {
+                kind,
+                traverse: Traverse::Ground,
+                glyph:    '"'
+            },
+        }
     }
 
-    pub fn fore_color(&self) -> Color {
-        self.fore_color
+    pub fn kind(&self) -> TileKind {
+        self.kind
     }
 
-    pub fn back_color(&self) -> Color {
-        self.back_color
+    pub fn traverse(&self) -> Traverse {
+        self.traverse
     }
 
     pub fn glyph(&self) -> char {
@@ -105,8 +148,9 @@ impl Tile {
 
 #[derive(Debug)]
 pub struct Board {
-    size:  Dimension,
-    tiles: Vec<Tile>,
+    size:     Dimension,
+    topology: Topology,
+    tiles:    Vec<Tile>,
     
     entities: Vec<Option<EntityIndex>>
 }
@@ -114,7 +158,8 @@ pub struct Board {
 impl Board {
     pub fn new(size: Dimension) -> Self {
         Board {
-            size: size,
+            size,
+            topology: Topology::Square,
             tiles:    {
                 let mut tiles = vec![Tile::new(TileKind::Floor); size.area() as usize];
                 for x in 0..size.width {
@@ -146,10 +191,30 @@ impl Board {
         self.size.height
     }
 
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
+
+    /// The positions one step away, whether or not they are on the board.
+    pub fn neighbours(&self, position: Position) -> impl Iterator<Item = Position> {
+        self.topology.neighbours(position)
+    }
+
+    /// The number of steps between two positions.
+    pub fn distance(&self, from: Position, to: Position) -> u32 {
+        self.topology.distance(from, to)
+    }
+
+    /// The index of the tile in row order, or None if the position is off
+    /// the board. Positions past either side of a row are off the board
+    /// rather than wrapping around onto the next row.
     pub fn to_index(&self, position: Position) -> Option<usize> {
-        let index = position.x + position.y * self.size.width as i32;
-        if index >= 0 && index < self.tiles.len() as i32 {
-            Some(index as usize)
+        if self.in_bounds(position) {
+            Some(self.to_index_unchecked(position))
         } else {
             None
         }
@@ -172,6 +237,12 @@ impl Board {
         }
     }
 
+    pub fn set_tile_at(&mut self, position: Position, kind: TileKind) {
+        if let Some(index) = self.to_index(position) {
+            self.tiles[index] = Tile::new(kind);
+        }
+    }
+
     pub fn swap_between(&mut self, from: Position, to: Position) {
         if let (Some(i), Some(j)) = (self.to_index(from), self.to_index(to)) {
             self.entities.swap(i, j);
@@ -203,7 +274,7 @@ impl Board {
     }
 
     pub fn navigation_map(&self, space: Option<Space>) -> NavMap {
-        let mut map = NavMap::new(self.width() as i32, self.height() as i32);
+        let mut map = NavMap::new(self.size, self.topology);
         for y in 0..self.height() {
             for x in 0..self.width() {
                 let tile = &self.tiles[(x + y * self.width()) as usize];
@@ -216,23 +287,116 @@ impl Board {
                     }
                 };
 
-                map.set(x as i32, y as i32, true, can_traverse);
+                map.set(Position::new(x as i32, y as i32), can_traverse);
             }
         }
 
         map
     }
 
-    pub fn in_range(&self, origin: Position, target: Position, range: u32, space: Option<Space>) -> bool {
-        let mut astar = {
-            let map = self.navigation_map(space);
-            AStar::new_from_map(map, 0.0)
+    /// Whether the tile is next to water.
+    pub fn is_coastal(&self, position: Position) -> bool {
+        self.neighbours(position)
+            .filter(|neighbour| self.in_bounds(*neighbour))
+            .any(|neighbour| self.tile_at(neighbour).is_some_and(|tile| tile.is_water()))
+    }
+
+    /// Whether a shot can be fired from one tile to another. Walls on the
+    /// line between them block it; the tiles at either end do not.
+    ///
+    /// A line drawn one way can pass through different tiles than the same
+    /// line drawn the other way, so the sight is clear if either is. That
+    /// keeps it symmetric, and a defender can always shoot back at an
+    /// attacker that could see it.
+    pub fn has_line_of_sight(&self, from: Position, to: Position) -> bool {
+        self.is_line_clear(from, to) || self.is_line_clear(to, from)
+    }
+
+    fn is_line_clear(&self, from: Position, to: Position) -> bool {
+        let is_clear = |position: Position| {
+            position == from || position == to || self.tile_at(position).is_some_and(|tile| !tile.is_wall())
         };
 
-        if astar.find(origin.into(), target.into()) {
-            astar.walk().count() as u32 <= range
-        } else {
-            false
+        match self.topology {
+            Topology::Square => from.line_to(&to).all(is_clear),
+            Topology::Hex    => from.hex_line_to(&to).all(is_clear)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board_with_walls(topology: Topology) -> Board {
+        let mut board = Board::new(Dimension::new(8, 8));
+        board.set_topology(topology);
+
+        for wall in [Position::new(3, 2), Position::new(4, 4), Position::new(2, 5)] {
+            board.set_tile_at(wall, TileKind::Wall);
+        }
+
+        board
+    }
+
+    #[test]
+    fn walls_block_line_of_sight() {
+        let board = board_with_walls(Topology::Square);
+
+        assert!(!board.has_line_of_sight(Position::new(1, 2), Position::new(5, 2)));
+        assert!(board.has_line_of_sight(Position::new(1, 1), Position::new(6, 1)));
+    }
+
+    #[test]
+    fn walls_at_either_end_do_not_block() {
+        let board = board_with_walls(Topology::Square);
+
+        assert!(board.has_line_of_sight(Position::new(3, 1), Position::new(3, 2)));
+    }
+
+    #[test]
+    fn hex_boards_measure_in_hex_steps() {
+        let board = board_with_walls(Topology::Hex);
+
+        assert_eq!(board.neighbours(Position::new(1, 1)).count(), 6);
+        assert_eq!(board.distance(Position::new(0, 0), Position::new(2, 2)), 4);
+        assert_eq!(board.distance(Position::new(2, 0), Position::new(0, 2)), 2);
+        assert!(!board.has_line_of_sight(Position::new(3, 1), Position::new(3, 3)));
+    }
+
+    #[test]
+    fn line_of_sight_is_symmetric() {
+        for topology in [Topology::Square, Topology::Hex] {
+            let board = board_with_walls(topology);
+            let tiles: Vec<Position> = Position::new(1, 1).rectangle(6, 6).collect();
+
+            for from in &tiles {
+                for to in &tiles {
+                    assert_eq!(
+                        board.has_line_of_sight(*from, *to),
+                        board.has_line_of_sight(*to, *from),
+                        "{:?} and {:?} on {:?}", from, to, topology
+                    );
+                }
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn positions_past_the_edge_of_a_row_are_off_the_board() {
+        let board = Board::new(Dimension::new(4, 4));
+
+        assert_eq!(board.to_index(Position::new(4, 1)), None);
+        assert_eq!(board.to_index(Position::new(-1, 2)), None);
+        assert_eq!(board.to_index(Position::new(3, 1)), Some(7));
+    }
+
+    #[test]
+    fn coast_ignores_tiles_on_the_next_row() {
+        let mut board = Board::new(Dimension::new(4, 4));
+        board.set_tile_at(Position::new(0, 2), TileKind::Ocean);
+
+        assert!(!board.is_coastal(Position::new(3, 1)));
+        assert!(board.is_coastal(Position::new(1, 2)));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{EntityIndex, Game, Unit, Special, Space};
+
+/// How much more or less damage one unit deals to another, as listed in
+/// the attacker's definition. Armour shrugs off small arms, vehicles run
+/// down infantry, engineers know where to hit a structure and submarines
+/// are hard to hit from the surface.
+pub fn matchup_modifier(game: &Game, attacker: &Unit, defender: &Unit) -> i32 {
+    game.definitions
+        .get(&attacker.kind)
+        .map_or(0, |definition| definition.matchup(&defender.kind))
+}
+
+/// Whether the attacker is able to hit the defender at all. Units in the
+/// air can only be attacked by units with the AntiAir special.
+pub fn can_target(attacker: &Unit, defender: &Unit) -> bool {
+    defender.space != Space::Air || attacker.has_special(Special::AntiAir)
+}
+
+/// The damage one unit deals to another when it attacks, after the
+/// matchup modifier, the anti-air bonus and the defense of the defender's
+/// tile. An attack always deals at least one damage if the attacker can
+/// deal any.
+pub fn damage_between(game: &Game, attacker: &Unit, defender: &Unit) -> u32 {
+    if attacker.damage == 0 {
+        return 0;
+    }
+
+    // Aircraft get no cover from the ground beneath them.
+    let defense = match defender.space {
+        Space::Air => 0,
+        _          => game.board.tile_at(defender.position).map_or(0, |tile| tile.kind().defense())
+    };
+
+    let anti_air = if defender.space == Space::Air && attacker.has_special(Special::AntiAir) {
+        1
+    } else {
+        0
+    };
+
+    let damage = attacker.damage as i32
+        + matchup_modifier(game, attacker, defender)
+        + anti_air
+        - defense as i32;
+
+    damage.max(1) as u32
+}
+
+/// Whether the defender can strike back at the attacker: it has to
+/// survive the attack, be able to target the attacker and have it in
+/// range and in its line of sight.
+pub fn can_retaliate(game: &Game, attacker: &Unit, defender: &Unit, damage: u32) -> bool {
+    // Units that explode are gone before anything can strike back.
+    game.rules.counterattacks
+        && !attacker.has_special(Special::Explode)
+        && defender.damage != 0
+        && can_target(defender, attacker)
+        && defender.health > damage
+        && game.board.distance(defender.position, attacker.position) <= defender.range
+        && game.board.has_line_of_sight(defender.position, attacker.position)
+}
+
+/// What an attack will do if nothing changes before the damage resolves.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Prediction {
+    /// The damage dealt to the defender.
+    pub damage:  u32,
+
+    /// The damage the defender deals back to the attacker.
+    pub counter: u32,
+
+    pub defender_health: u32,
+    pub attacker_health: u32
+}
+
+impl Prediction {
+    pub fn defender_destroyed(&self) -> bool {
+        self.defender_health == 0
+    }
+
+    pub fn attacker_destroyed(&self) -> bool {
+        self.attacker_health == 0
+    }
+}
+
+/// Predicts the outcome of one unit attacking another, without checking
+/// whether the attack is allowed.
+pub fn predict_attack(game: &Game, entity: EntityIndex, target_entity: EntityIndex) -> Option<Prediction> {
+    let attacker = game.units.get(entity)?;
+    let defender = game.units.get(target_entity)?;
+
+    let damage  = damage_between(game, attacker, defender);
+    let counter = if can_retaliate(game, attacker, defender, damage) {
+        damage_between(game, defender, attacker)
+    } else {
+        0
+    };
+
+    Some(Prediction {
+        damage,
+        counter,
+        defender_health: defender.health.saturating_sub(damage),
+        attacker_health: if attacker.has_special(Special::Explode) {
+            0
+        } else {
+            attacker.health.saturating_sub(counter)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Position, Team, TileKind, UnitKind, SpawnData, read_definitions};
+
+    fn duel(attacker: UnitKind, defender: UnitKind, distance: i32) -> (Game, EntityIndex, EntityIndex) {
+        let mut game = Game::new(Board::new(Dimension::new(8, 5)));
+
+        let attacker = game.spawn(SpawnData::new(attacker, Team::Red, Position::new(1, 2))).unwrap();
+        let defender = game.spawn(SpawnData::new(defender, Team::Blue, Position::new(1 + distance, 2))).unwrap();
+
+        (game, attacker, defender)
+    }
+
+    #[test]
+    fn tanks_run_down_infantry() {
+        let (game, tank, infantry) = duel(UnitKind::TANK, UnitKind::INFANTRY, 1);
+
+        assert_eq!(predict_attack(&game, tank, infantry), Some(Prediction {
+            damage:          3,
+            counter:         0,
+            defender_health: 0,
+            attacker_health: 4
+        }));
+    }
+
+    #[test]
+    fn matchups_come_from_the_definitions() {
+        let (mut game, attacker, defender) = duel(UnitKind::TANK, UnitKind::TANK, 1);
+        assert_eq!(matchup_modifier(&game, &game.units[attacker], &game.units[defender]), 0);
+
+        game.definitions = read_definitions("[Tank]\nmatchups = Tank +2\n").unwrap();
+        assert_eq!(predict_attack(&game, attacker, defender).unwrap().damage, 4);
+    }
+
+    #[test]
+    fn survivors_strike_back_within_their_range() {
+        let (game, infantry, tank) = duel(UnitKind::INFANTRY, UnitKind::TANK, 1);
+        let prediction = predict_attack(&game, infantry, tank).unwrap();
+
+        assert_eq!(prediction.damage, 1);
+        assert_eq!(prediction.counter, 3);
+        assert!(prediction.attacker_destroyed());
+        assert!(!prediction.defender_destroyed());
+
+        let (game, attacker, defender) = duel(UnitKind::TANK, UnitKind::TANK, 3);
+        assert_eq!(predict_attack(&game, attacker, defender).unwrap().counter, 2);
+    }
+
+    #[test]
+    fn no_counterattack_without_the_rule_or_a_line_of_sight() {
+        let (mut game, attacker, defender) = duel(UnitKind::TANK, UnitKind::TANK, 2);
+        game.rules.counterattacks = false;
+        assert_eq!(predict_attack(&game, attacker, defender).unwrap().counter, 0);
+
+        game.rules.counterattacks = true;
+        game.board.set_tile_at(Position::new(2, 2), TileKind::Wall);
+        assert_eq!(predict_attack(&game, attacker, defender).unwrap().counter, 0);
+    }
+
+    #[test]
+    fn aircraft_are_hit_only_by_anti_air() {
+        let (game, fighter, tank) = duel(UnitKind::FIGHTER, UnitKind::TANK, 1);
+        assert!(!can_target(&game.units[tank], &game.units[fighter]));
+        assert_eq!(predict_attack(&game, fighter, tank).unwrap().counter, 0);
+
+        let (mut game, humvee, fighter) = duel(UnitKind::HUMVEE, UnitKind::FIGHTER, 1);
+        game.board.set_tile_at(Position::new(2, 2), TileKind::Forest);
+        assert!(can_target(&game.units[humvee], &game.units[fighter]));
+        assert_eq!(predict_attack(&game, humvee, fighter).unwrap().damage, 2);
+    }
+
+    #[test]
+    fn cover_and_missiles() {
+        let (mut game, attacker, defender) = duel(UnitKind::TANK, UnitKind::TANK, 1);
+        game.board.set_tile_at(Position::new(2, 2), TileKind::Forest);
+        assert_eq!(predict_attack(&game, attacker, defender).unwrap().damage, 1);
+
+        let (game, missile, tank) = duel(UnitKind::MISSILE, UnitKind::TANK, 2);
+        assert!(predict_attack(&game, missile, tank).unwrap().attacker_destroyed());
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{HashMap};
+use std::fs;
+use std::path::{Path};
+use std::sync::{OnceLock};
+
+use crate::{AbilityKind};
+use crate::entity::*;
+
+/// The definitions shipped with the game. They are the defaults for every
+/// game, so the statistics live in the file and nowhere else.
+const SHIPPED_DEFINITIONS: &str = include_str!("../res/Units.def");
+
+/// The unit definitions used by a game, keyed by kind.
+///
+/// Definitions files are applied on top of the shipped definitions one
+/// kind at a time. Each entry starts with the kind in square brackets and
+/// is followed by `key = value` lines. An entry for a kind that is already
+/// defined only changes the keys it lists, while an entry for a new name
+/// adds a new kind. Lines starting with `;` are comments.
+///
+/// ```text
+/// [Tank]
+/// name     = Tank
+/// glyph    = 133
+/// space    = Ground
+/// class    = Vehicle
+/// health   = 4
+/// damage   = 2
+/// range    = 3
+/// sight    = 4
+/// actions  = 2
+/// fuel     = 0
+/// capacity = 0
+/// cost     = 300
+/// specials =
+/// roster   =
+/// matchups = Infantry +1, Engineer +1
+/// build    = 0
+/// ```
+///
+/// Units with the Explode special also describe their blast with `blast`
+/// (the radius), `shape` (Diamond, Square or Cross), `falloff`, `blocked`
+/// and `friendly`, the last two being `true` or `false`.
+#[derive(Debug, Clone)]
+pub struct Definitions {
+    units: Vec<UnitDefinition>,
+    index: HashMap<UnitKind, usize>
+}
+
+/// A problem with one entry of a definitions file.
+#[derive(Debug)]
+pub struct DefinitionError {
+    pub entry:  String,
+    pub line:   usize,
+    pub reason: String
+}
+
+impl Default for Definitions {
+    fn default() -> Self {
+        static SHIPPED: OnceLock<Definitions> = OnceLock::new();
+
+        SHIPPED
+            .get_or_init(|| {
+                apply_definitions(Definitions::empty(), SHIPPED_DEFINITIONS)
+                    .expect("the shipped unit definitions are valid")
+            })
+            .clone()
+    }
+}
+
+impl Definitions {
+    pub fn empty() -> Self {
+        Definitions {
+            units: Vec::new(),
+            index: HashMap::new()
+        }
+    }
+
+    pub fn get(&self, kind: &UnitKind) -> Option<&UnitDefinition> {
+        self.index.get(kind).map(|index| &self.units[*index])
+    }
+
+    pub fn insert(&mut self, definition: UnitDefinition) {
+        match self.index.get(&definition.kind) {
+            Some(index) => self.units[*index] = definition,
+            None => {
+                self.index.insert(definition.kind.clone(), self.units.len());
+                self.units.push(definition);
+            }
+        }
+    }
+
+    /// The orders a unit of the kind can be given, or none if the kind is
+    /// not defined.
+    pub fn abilities(&self, kind: &UnitKind) -> Vec<AbilityKind> {
+        self.get(kind).map(UnitDefinition::abilities).unwrap_or_default()
+    }
+
+    /// Every definition, in the order the kinds were first defined.
+    pub fn all(&self) -> impl Iterator<Item = &UnitDefinition> {
+        self.units.iter()
+    }
+}
+
+pub fn load_definitions(path: &Path) -> Result<Definitions, Vec<DefinitionError>> {
+    let text = fs::read_to_string(path).map_err(|error| vec![DefinitionError {
+        entry:  path.display().to_string(),
+        line:   0,
+        reason: format!("{}", error)
+    }])?;
+
+    read_definitions(&text)
+}
+
+/// Parses a definitions file on top of the shipped definitions. Every
+/// invalid entry is reported rather than stopping at the first one.
+pub fn read_definitions(text: &str) -> Result<Definitions, Vec<DefinitionError>> {
+    apply_definitions(Definitions::default(), text)
+}
+
+fn apply_definitions(mut definitions: Definitions, text: &str) -> Result<Definitions, Vec<DefinitionError>> {
+    let mut errors = Vec::new();
+
+    // Rosters and matchups may name kinds defined further down, so they
+    // are checked once every entry has been read.
+    let mut references: Vec<(String, usize, UnitKind)> = Vec::new();
+
+    let mut entry: Option<(String, usize, Option<UnitDefinition>)> = None;
+
+    for (i, text) in text.lines().enumerate() {
+        let line    = i + 1;
+        let trimmed = text.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            if let Some((_, _, Some(definition))) = entry.take() {
+                definitions.insert(definition);
+            }
+
+            let name = String::from(&trimmed[1..trimmed.len() - 1]);
+            let definition = match name.parse::<UnitKind>() {
+                Ok(kind) => Some(definitions.get(&kind).cloned().unwrap_or_else(|| {
+                    DefinitionBuilder::new()
+                        .with_kind(kind)
+                        .with_name(name.clone())
+                        .build()
+                })),
+
+                Err(()) => {
+                    errors.push(DefinitionError {
+                        entry:  name.clone(),
+                        line,
+                        reason: format!("invalid unit kind '{}'", name)
+                    });
+
+                    None
+                }
+            };
+
+            entry = Some((name, line, definition));
+            continue;
+        }
+
+        let (name, _, definition) = match &mut entry {
+            Some(entry) => entry,
+            None => {
+                errors.push(DefinitionError {
+                    entry:  String::new(),
+                    line,
+                    reason: String::from("property outside of an entry")
+                });
+
+                continue;
+            }
+        };
+
+        let definition = match definition {
+            Some(definition) => definition,
+            None             => continue
+        };
+
+        let mut error = |reason: String| {
+            errors.push(DefinitionError {
+                entry: name.clone(),
+                line,
+                reason
+            });
+        };
+
+        let (key, value) = match trimmed.find('=') {
+            Some(index) => (trimmed[..index].trim(), trimmed[index + 1..].trim()),
+            None => {
+                error(String::from("expected 'key = value'"));
+                continue;
+            }
+        };
+
+        match key {
+            "name" => {
+                if value.is_empty() {
+                    error(String::from("name cannot be empty"));
+                } else {
+                    definition.name = String::from(value);
+                }
+            },
+
+            "glyph" => {
+                match value.parse::<u32>().ok().and_then(std::char::from_u32) {
+                    Some(glyph) => definition.glyph = glyph,
+                    None        => error(format!("invalid glyph code '{}'", value))
+                }
+            },
+
+            "class" => {
+                match value.parse() {
+                    Ok(class) => definition.class = class,
+                    Err(())   => error(format!("unknown class '{}'", value))
+                }
+            },
+
+            "space" => {
+                match value.parse() {
+                    Ok(space) => definition.space = space,
+                    Err(())   => error(format!("unknown space '{}'", value))
+                }
+            },
+
+            "health" => {
+                match value.parse() {
+                    Ok(health) if health > 0 => definition.health = health,
+                    _                        => error(format!("health must be a positive number, not '{}'", value))
+                }
+            },
+
+            "damage" | "range" | "sight" | "actions" | "fuel" | "capacity" | "cost" | "build" | "blast" | "falloff" => {
+                let number = match value.parse() {
+                    Ok(number) => number,
+                    Err(_)     => {
+                        error(format!("{} must be a number, not '{}'", key, value));
+                        continue;
+                    }
+                };
+
+                match key {
+                    "damage"   => definition.damage   = number,
+                    "range"    => definition.range    = number,
+                    "sight"    => definition.sight    = number,
+                    "actions"  => definition.actions  = number,
+                    "fuel"     => definition.fuel     = number,
+                    "capacity" => definition.capacity = number,
+                    "build"    => definition.build    = number,
+                    "blast"    => definition.explosion.radius  = number,
+                    "falloff"  => definition.explosion.falloff = number,
+                    _          => definition.cost     = number
+                }
+            },
+
+            "roster" => {
+                definition.roster.clear();
+                for kind in value.split(',').map(str::trim).filter(|kind| !kind.is_empty()) {
+                    match kind.parse::<UnitKind>() {
+                        Ok(kind) => {
+                            references.push((name.clone(), line, kind.clone()));
+                            definition.roster.push(kind);
+                        },
+
+                        Err(()) => error(format!("invalid unit kind '{}'", kind))
+                    }
+                }
+            },
+
+            "matchups" => {
+                definition.matchups.clear();
+                for matchup in value.split(',').map(str::trim).filter(|matchup| !matchup.is_empty()) {
+                    let words: Vec<&str> = matchup.split_whitespace().collect();
+                    match words.as_slice() {
+                        [kind, modifier] => match (kind.parse::<UnitKind>(), modifier.parse()) {
+                            (Ok(kind), Ok(modifier)) => {
+                                references.push((name.clone(), line, kind.clone()));
+                                definition.matchups.push((kind, modifier));
+                            },
+
+                            (Err(()), _) => error(format!("invalid unit kind '{}'", kind)),
+                            (_, Err(_))  => error(format!("invalid modifier '{}'", modifier))
+                        },
+
+                        _ => error(format!("expected 'Kind +N' or 'Kind -N', not '{}'", matchup))
+                    }
+                }
+            },
+
+            "shape" => {
+                match value.parse() {
+                    Ok(shape) => definition.explosion.shape = shape,
+                    Err(())   => error(format!("unknown blast shape '{}'", value))
+                }
+            },
+
+            "blocked" | "friendly" => {
+                let flag = match value.parse() {
+                    Ok(flag) => flag,
+                    Err(_)   => {
+                        error(format!("{} must be true or false, not '{}'", key, value));
+                        continue;
+                    }
+                };
+
+                match key {
+                    "blocked" => definition.explosion.blocked_by_walls = flag,
+                    _         => definition.explosion.friendly_fire    = flag
+                }
+            },
+
+            "specials" => {
+                definition.specials.clear();
+                for special in value.split(',').map(str::trim).filter(|special| !special.is_empty()) {
+                    match special.parse() {
+                        Ok(special) => definition.specials.push(special),
+                        Err(())     => error(format!("unknown special '{}'", special))
+                    }
+                }
+            },
+
+            _ => {
+                error(format!("unknown property '{}'", key));
+            }
+        }
+    }
+
+    if let Some((_, _, Some(definition))) = entry.take() {
+        definitions.insert(definition);
+    }
+
+    for (entry, line, kind) in references {
+        if definitions.get(&kind).is_none() {
+            errors.push(DefinitionError {
+                entry,
+                line,
+                reason: format!("names undefined kind '{:?}'", kind)
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(definitions)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_definitions_cover_every_named_kind() {
+        let definitions = Definitions::default();
+
+        assert!(definitions.get(&UnitKind::TANK).is_some());
+        assert!(definitions.get(&UnitKind::AIRBASE).is_some());
+        assert!(definitions.get(&UnitKind::UNKNOWN).is_none());
+        assert_eq!(definitions.all().next().map(|definition| &definition.kind), Some(&UnitKind::ENGINEER));
+    }
+
+    #[test]
+    fn entries_only_change_the_keys_they_list() {
+        let definitions = read_definitions("[Tank]\nhealth = 9\n").unwrap();
+        let tank        = definitions.get(&UnitKind::TANK).unwrap();
+        let shipped     = Definitions::default();
+
+        assert_eq!(tank.health, 9);
+        assert_eq!(tank.damage, shipped.get(&UnitKind::TANK).unwrap().damage);
+    }
+
+    #[test]
+    fn new_names_add_new_kinds() {
+        let text = "\
+[Sniper]
+glyph    = 115
+class    = Infantry
+health   = 2
+damage   = 2
+range    = 4
+actions  = 1
+
+[Barracks]
+roster   = Infantry, Sniper
+";
+
+        let definitions = read_definitions(text).unwrap();
+        let sniper      = definitions.get(&UnitKind::named("Sniper")).unwrap();
+
+        assert_eq!(sniper.name, "Sniper");
+        assert_eq!(sniper.range, 4);
+        assert_eq!(sniper.class, UnitClass::Infantry);
+        assert!(definitions.get(&UnitKind::BARRACKS).unwrap().roster.contains(&sniper.kind));
+    }
+
+    #[test]
+    fn every_invalid_entry_is_reported() {
+        let text = "\
+health = 3
+
+[Tank]
+health = none
+speed  = 4
+
+[Humvee]
+space    = Space
+specials = Build, Flight
+
+[Barracks]
+roster   = Infantry, Ghost
+
+[Not a kind]
+health = 1
+";
+
+        let errors = read_definitions(text).unwrap_err();
+        let lines: Vec<(&str, usize)> = errors
+            .iter()
+            .map(|error| (error.entry.as_str(), error.line))
+            .collect();
+
+        assert_eq!(lines, vec![
+            ("",           1),
+            ("Tank",       4),
+            ("Tank",       5),
+            ("Humvee",     8),
+            ("Humvee",     9),
+            ("Not a kind", 14),
+            ("Barracks",   12)
+        ]);
+    }
+}
@@ -1,8 +1,13 @@
-use tcod::colors::*;
+use std::borrow::{Cow};
+use std::fmt;
+use std::str::{FromStr};
+
 use crate::position::*;
 use crate::board::{Traverse};
+use crate::ability::{AbilityKind};
+use crate::explosion::{ExplosionProfile};
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum Team {
     Red,
     Blue,
@@ -14,17 +19,33 @@ pub enum Team {
     White
 }
 
-impl Team {
-    pub fn color(&self) -> Color {
-        match self {
-            Team::Red     => RED,
-            Team::Blue    => BLUE,
-            Team::Green   => GREEN,
-            Team::Yellow  => YELLOW,
-            Team::Cyan    => CYAN,
-            Team::Orange  => ORANGE,
-            Team::Magenta => MAGENTA,
-            Team::White   => WHITE
+pub fn get_next_team(team: Team) -> Team {
+    match team {
+        Team::Red     => Team::Blue,
+        Team::Blue    => Team::Green,
+        Team::Green   => Team::Yellow,
+        Team::Yellow  => Team::Cyan,
+        Team::Cyan    => Team::Orange,
+        Team::Orange  => Team::Magenta,
+        Team::Magenta => Team::White,
+        Team::White   => Team::Red
+    }
+}
+
+impl FromStr for Team {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Red"     => Ok(Team::Red),
+            "Blue"    => Ok(Team::Blue),
+            "Green"   => Ok(Team::Green),
+            "Yellow"  => Ok(Team::Yellow),
+            "Cyan"    => Ok(Team::Cyan),
+            "Orange"  => Ok(Team::Orange),
+            "Magenta" => Ok(Team::Magenta),
+            "White"   => Ok(Team::White),
+            _         => Err(())
         }
     }
 }
@@ -50,225 +71,515 @@ impl Space {
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
-pub enum UnitKind {
-    Unknown,
-    Engineer,
+impl FromStr for Space {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Ground" => Ok(Space::Ground),
+            "Water"  => Ok(Space::Water),
+            "Air"    => Ok(Space::Air),
+            _        => Err(())
+        }
+    }
+}
+
+/// Identifies a unit archetype by the name of its entry in the definitions
+/// file, so new kinds can be added without recompiling. The shipped kinds
+/// borrow their names; kinds read from a file own theirs.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+pub struct UnitKind(Cow<'static, str>);
+
+impl UnitKind {
+    pub const UNKNOWN:    UnitKind = UnitKind(Cow::Borrowed("Unknown"));
+    pub const ENGINEER:   UnitKind = UnitKind(Cow::Borrowed("Engineer"));
+    pub const INFANTRY:   UnitKind = UnitKind(Cow::Borrowed("Infantry"));
+    pub const MEDIC:      UnitKind = UnitKind(Cow::Borrowed("Medic"));
+    pub const HUMVEE:     UnitKind = UnitKind(Cow::Borrowed("Humvee"));
+    pub const TANK:       UnitKind = UnitKind(Cow::Borrowed("Tank"));
+    pub const MISSILE:    UnitKind = UnitKind(Cow::Borrowed("Missile"));
+    pub const FLAG:       UnitKind = UnitKind(Cow::Borrowed("Flag"));
+    pub const BARRACKS:   UnitKind = UnitKind(Cow::Borrowed("Barracks"));
+    pub const FACTORY:    UnitKind = UnitKind(Cow::Borrowed("Factory"));
+    pub const AIRBASE:    UnitKind = UnitKind(Cow::Borrowed("Airbase"));
+    pub const HARBOR:     UnitKind = UnitKind(Cow::Borrowed("Harbor"));
+    pub const GUNBOAT:    UnitKind = UnitKind(Cow::Borrowed("Gunboat"));
+    pub const TRANSPORT:  UnitKind = UnitKind(Cow::Borrowed("Transport"));
+    pub const SUBMARINE:  UnitKind = UnitKind(Cow::Borrowed("Submarine"));
+    pub const CARRIER:    UnitKind = UnitKind(Cow::Borrowed("Carrier"));
+    pub const FIGHTER:    UnitKind = UnitKind(Cow::Borrowed("Fighter"));
+    pub const BOMBER:     UnitKind = UnitKind(Cow::Borrowed("Bomber"));
+    pub const HELICOPTER: UnitKind = UnitKind(Cow::Borrowed("Helicopter"));
+
+    /// The kind with the name, which does not have to be defined yet.
+    pub fn named(name: &str) -> UnitKind {
+        UnitKind(Cow::Owned(name.to_owned()))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for UnitKind {
+    type Err = ();
+
+    /// Accepts any name made of letters, digits and underscores. Whether
+    /// the kind is defined is up to the definitions in use.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            Ok(UnitKind::named(name))
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl fmt::Debug for UnitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The broad role of a unit.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum UnitClass {
+    /// Fights on foot.
     Infantry,
-    Humvee,
-    Tank,
-    Missile,
-    Flag,
-    Barracks,
-    Factory,
-    Airbase
+
+    /// Ground vehicles and aircraft.
+    Vehicle,
+
+    /// Does not move.
+    Structure
+}
+
+impl FromStr for UnitClass {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Infantry"  => Ok(UnitClass::Infantry),
+            "Vehicle"   => Ok(UnitClass::Vehicle),
+            "Structure" => Ok(UnitClass::Structure),
+            _           => Err(())
+        }
+    }
 }
 
-struct UnitBuilder {
+/// A rule that sets a unit apart from the others beyond its statistics.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum Special {
+    /// The unit can construct structures.
+    Build,
+
+    /// The unit is destroyed when it attacks, damaging the area around
+    /// its target.
+    Explode,
+
+    /// The unit can attack units in the air and deals them extra damage.
+    AntiAir,
+
+    /// Aircraft next to the unit are refuelled at the start of their turn.
+    Refuel,
+
+    /// The unit can repair adjacent vehicles and structures.
+    Repair,
+
+    /// The unit can heal adjacent infantry.
+    Heal,
+
+    /// Infantry can shelter inside the unit and recover when their team
+    /// ends its turn. Nothing else can board it.
+    Shelter,
+
+    /// The structure has to be built next to water.
+    Coastal,
+
+    /// The team is eliminated in capture the flag once it has no units
+    /// with this special left.
+    Objective
+}
+
+impl FromStr for Special {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Build"     => Ok(Special::Build),
+            "Explode"   => Ok(Special::Explode),
+            "AntiAir"   => Ok(Special::AntiAir),
+            "Refuel"    => Ok(Special::Refuel),
+            "Repair"    => Ok(Special::Repair),
+            "Heal"      => Ok(Special::Heal),
+            "Shelter"   => Ok(Special::Shelter),
+            "Coastal"   => Ok(Special::Coastal),
+            "Objective" => Ok(Special::Objective),
+            _           => Err(())
+        }
+    }
+}
+
+/// The statistics shared by every unit of a kind.
+#[derive(Debug, Clone)]
+pub struct UnitDefinition {
+    pub kind:     UnitKind,
+    pub name:     String,
+    pub glyph:    char,
+    pub space:    Space,
+    pub class:    UnitClass,
+    pub health:   u32,
+    pub damage:   u32,
+    pub range:    u32,
+    pub sight:    u32,
+    pub actions:  u32,
+
+    /// The turns the unit can stay out before it has to refuel, or zero
+    /// if it never needs to.
+    pub fuel:     u32,
+
+    /// The number of units the unit can carry.
+    pub capacity: u32,
+    pub cost:     u32,
+    pub specials: Vec<Special>,
+
+    /// The kinds a structure is able to produce.
+    pub roster:   Vec<UnitKind>,
+
+    /// The extra damage dealt to units of other kinds, which may be
+    /// negative.
+    pub matchups: Vec<(UnitKind, i32)>,
+
+    /// The actions an Engineer spends to build the structure, or zero if
+    /// it cannot be built.
+    pub build:    u32,
+
+    /// The blast dealt by a unit with the Explode special.
+    pub explosion: ExplosionProfile
+}
+
+pub struct DefinitionBuilder {
     kind:     UnitKind,
-    team:     Team,
     name:     String,
     glyph:    char,
     space:    Space,
+    class:    UnitClass,
     health:   u32,
     damage:   u32,
     range:    u32,
+    sight:    u32,
     actions:  u32,
-    position: Position
+    fuel:     u32,
+    capacity: u32,
+    cost:     u32,
+    specials: Vec<Special>,
+    roster:   Vec<UnitKind>,
+    matchups: Vec<(UnitKind, i32)>,
+    build:    u32,
+
+    explosion: ExplosionProfile
 }
 
-impl UnitBuilder {
-    fn new() -> Self {
-        UnitBuilder {
-            kind:    UnitKind::Unknown,
-            team:    Team::White,
-            name:    String::from("No Name"),
-            glyph:   '?',
-            space:   Space::Ground,
-            health:  1,
-            damage:  1,
-            range:   1,
-            actions: 1,
-            position: Position::default()
+impl DefinitionBuilder {
+    pub fn new() -> Self {
+        DefinitionBuilder {
+            kind:     UnitKind::UNKNOWN,
+            name:     String::from("No Name"),
+            glyph:    '?',
+            space:    Space::Ground,
+            class:    UnitClass::Vehicle,
+            health:   1,
+            damage:   1,
+            range:    1,
+            sight:    3,
+            actions:  1,
+            fuel:     0,
+            capacity: 0,
+            cost:     0,
+            specials: Vec::new(),
+            roster:   Vec::new(),
+            matchups: Vec::new(),
+            build:    0,
+
+            explosion: ExplosionProfile::default()
         }
     }
 
-    fn with_kind(mut self, kind: UnitKind) -> Self {
+    pub fn with_kind(mut self, kind: UnitKind) -> Self {
         self.kind = kind;
         self
     }
 
-    fn with_team(mut self, team: Team) -> Self {
-        self.team = team;
-        self
-    }
-
-    fn with_name(mut self, name: String) -> Self {
+    pub fn with_name(mut self, name: String) -> Self {
         self.name = name;
         self
     }
 
-    fn with_glyph(mut self, glyph: char) -> Self {
+    pub fn with_glyph(mut self, glyph: char) -> Self {
         self.glyph = glyph;
         self
     }
 
-    fn with_space(mut self, space: Space) -> Self {
+    pub fn with_space(mut self, space: Space) -> Self {
         self.space = space;
         self
     }
 
-    fn with_health(mut self, health: u32) -> Self {
+    pub fn with_class(mut self, class: UnitClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn with_health(mut self, health: u32) -> Self {
         self.health = health;
         self
     }
 
-    fn with_damage(mut self, damage: u32) -> Self {
+    pub fn with_damage(mut self, damage: u32) -> Self {
         self.damage = damage;
         self
     }
 
-    fn with_range(mut self, range: u32) -> Self {
+    pub fn with_range(mut self, range: u32) -> Self {
         self.range = range;
         self
     }
 
-    fn with_actions(mut self, actions: u32) -> Self {
+    pub fn with_sight(mut self, sight: u32) -> Self {
+        self.sight = sight;
+        self
+    }
+
+    pub fn with_actions(mut self, actions: u32) -> Self {
         self.actions = actions;
         self
     }
 
-    fn with_position(mut self, position: Position) -> Self {
-        self.position = position;
+    pub fn with_fuel(mut self, fuel: u32) -> Self {
+        self.fuel = fuel;
         self
     }
 
-    fn build(self) -> Unit {
-        Unit {
-            kind:        self.kind,
-            team:        self.team,
-            name:        self.name,
-            glyph:       self.glyph,
-            space:       self.space,
-            health:      self.health,
-            health_max:  self.health,
-            damage:      self.damage,
-            range:       self.range,
-            actions:     self.actions,
-            actions_max: self.actions,
-            position:    self.position
+    pub fn with_capacity(mut self, capacity: u32) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_cost(mut self, cost: u32) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    pub fn with_special(mut self, special: Special) -> Self {
+        if !self.specials.contains(&special) {
+            self.specials.push(special);
+        }
+
+        self
+    }
+
+    pub fn with_roster(mut self, roster: Vec<UnitKind>) -> Self {
+        self.roster = roster;
+        self
+    }
+
+    pub fn with_matchup(mut self, defender: UnitKind, modifier: i32) -> Self {
+        self.matchups.push((defender, modifier));
+        self
+    }
+
+    pub fn with_build(mut self, build: u32) -> Self {
+        self.build = build;
+        self
+    }
+
+    pub fn with_explosion(mut self, explosion: ExplosionProfile) -> Self {
+        self.explosion = explosion;
+        self
+    }
+
+    pub fn build(self) -> UnitDefinition {
+        UnitDefinition {
+            kind:     self.kind,
+            name:     self.name,
+            glyph:    self.glyph,
+            space:    self.space,
+            class:    self.class,
+            health:   self.health,
+            damage:   self.damage,
+            range:    self.range,
+            sight:    self.sight,
+            actions:  self.actions,
+            fuel:     self.fuel,
+            capacity: self.capacity,
+            cost:     self.cost,
+            specials: self.specials,
+            roster:   self.roster,
+            matchups: self.matchups,
+            build:    self.build,
+
+            explosion: self.explosion
+        }
+    }
+}
+
+impl Default for DefinitionBuilder {
+    fn default() -> Self {
+        DefinitionBuilder::new()
+    }
+}
+
+impl UnitDefinition {
+    /// The orders a unit of this kind can be given, which follow from its
+    /// statistics and specials.
+    pub fn abilities(&self) -> Vec<AbilityKind> {
+        let mobile    = self.class != UnitClass::Structure;
+        let mut kinds = Vec::new();
+
+        if mobile && self.actions > 0 {
+            kinds.push(AbilityKind::Move);
+        }
+
+        if mobile && self.damage > 0 {
+            kinds.push(AbilityKind::Attack);
+        }
+
+        if self.has_special(Special::Build) {
+            kinds.push(AbilityKind::Build);
+        }
+
+        if self.has_special(Special::Repair) || self.has_special(Special::Heal) {
+            kinds.push(AbilityKind::Heal);
+        }
+
+        if self.class == UnitClass::Infantry {
+            kinds.push(AbilityKind::Embark);
+        }
+
+        if self.capacity > 0 {
+            kinds.push(AbilityKind::Disembark);
+        }
+
+        if self.has_special(Special::Explode) {
+            kinds.push(AbilityKind::Detonate);
+        }
+
+        if !self.roster.is_empty() {
+            kinds.push(AbilityKind::Produce);
+        }
+
+        kinds
+    }
+
+    /// The number of actions an Engineer must spend to build the
+    /// structure, or None if it cannot be built.
+    pub fn build_cost(&self) -> Option<u32> {
+        if self.build != 0 {
+            Some(self.build)
+        } else {
+            None
         }
     }
+
+    /// The extra damage the kind deals to the defending kind.
+    pub fn matchup(&self, defender: &UnitKind) -> i32 {
+        self.matchups
+            .iter()
+            .filter(|(kind, _)| kind == defender)
+            .map(|(_, modifier)| modifier)
+            .sum()
+    }
+
+    pub fn has_special(&self, special: Special) -> bool {
+        self.specials.contains(&special)
+    }
 }
 
 #[derive(Debug)]
 pub struct Unit {
+    /// Identifies the unit for as long as it exists. Unlike its entity
+    /// index it survives boarding a transport and saving the game.
+    pub id:          u32,
+
     pub kind:        UnitKind,
     pub team:        Team,
     pub name:        String,
     pub glyph:       char,
     pub space:       Space,
+    pub class:       UnitClass,
     pub health:      u32,
     pub health_max:  u32,
     pub damage:      u32,
     pub range:       u32,
+    pub sight:       u32,
     pub actions:     u32,
     pub actions_max: u32,
-    pub position:    Position
+    pub fuel:        u32,
+    pub fuel_max:    u32,
+    pub capacity:    u32,
+    pub position:    Position,
+    pub specials:    Vec<Special>,
+    pub explosion:   ExplosionProfile,
+
+    /// The units being carried. They are taken off the board and out of
+    /// the arena until they are unloaded.
+    pub cargo:       Vec<Unit>
 }
 
 impl Unit {
-    pub fn new(kind: UnitKind, team: Team, position: Position) -> Self {
-        let mut builder = UnitBuilder::new()
-            .with_kind(kind)
-            .with_team(team)
-            .with_position(position);
-        
-        match kind {
-            UnitKind::Unknown => {
-                panic!("Cannot create unit with kind 'Unknown.'");
-            },
-
-            UnitKind::Engineer => {
-                builder = builder
-                    .with_name(String::from("Engineer"))
-                    .with_glyph('\u{0080}')
-                    .with_space(Space::Ground)
-                    .with_health(1)
-                    .with_damage(1)
-                    .with_range(1)
-                    .with_actions(2)
-            },
-
-            UnitKind::Infantry => {
-                builder = builder
-                    .with_name(String::from("Infantry"))
-                    .with_glyph('\u{0081}')
-                    .with_space(Space::Ground)
-                    .with_health(2)
-                    .with_damage(1)
-                    .with_range(1)
-                    .with_actions(2)
-            },
-
-            UnitKind::Humvee => {
-                builder = builder
-                    .with_name(String::from("Humvee"))
-                    .with_glyph('\u{0083}')
-                    .with_space(Space::Ground)
-                    .with_health(3)
-                    .with_damage(1)
-                    .with_range(1)
-                    .with_actions(3)
-            },
-
-            UnitKind::Tank => {
-                builder = builder
-                    .with_name(String::from("Tank"))
-                    .with_glyph('\u{0085}')
-                    .with_space(Space::Ground)
-                    .with_health(4)
-                    .with_damage(2)
-                    .with_range(3)
-                    .with_actions(2)
-            }
-
-            UnitKind::Missile => {
-                builder = builder
-                    .with_name(String::from("Missile"))
-                    .with_glyph('\u{0082}')
-                    .with_space(Space::Air)
-                    .with_health(3)
-                    .with_damage(10)
-                    .with_range(3)
-                    .with_actions(3)
-            },
-
-            UnitKind::Flag => {
-                builder = builder
-                    .with_name(String::from("Flag"))
-                    .with_glyph('\u{0084}')
-                    .with_space(Space::Ground)
-                    .with_health(1)
-                    .with_damage(0)
-                    .with_range(0)
-                    .with_actions(1)
-            },
-
-            UnitKind::Barracks => {
-                builder = builder
-                    .with_name(String::from("Barracks"))
-                    .with_glyph('\u{0091}')
-                    .with_space(Space::Ground)
-                    .with_health(3)
-                    .with_actions(2)
-            },
-
-            _ => {
-                unimplemented!();
-            }
+    pub fn new(definition: &UnitDefinition, team: Team, position: Position) -> Self {
+        Unit {
+            id:          0,
+            kind:        definition.kind.clone(),
+            team,
+            name:        definition.name.clone(),
+            glyph:       definition.glyph,
+            space:       definition.space,
+            class:       definition.class,
+            health:      definition.health,
+            health_max:  definition.health,
+            damage:      definition.damage,
+            range:       definition.range,
+            sight:       definition.sight,
+            actions:     definition.actions,
+            actions_max: definition.actions,
+            fuel:        definition.fuel,
+            fuel_max:    definition.fuel,
+            capacity:    definition.capacity,
+            position,
+            specials:    definition.specials.clone(),
+            explosion:   definition.explosion,
+            cargo:       Vec::new()
         }
+    }
 
-        builder.build()
+    pub fn has_special(&self, special: Special) -> bool {
+        self.specials.contains(&special)
     }
-}
\ No newline at end of file
+
+    /// Whether the unit runs out of fuel when it stays away from an
+    /// Airbase or carrier.
+    pub fn uses_fuel(&self) -> bool {
+        self.fuel_max != 0
+    }
+
+    /// Moves the unit and everything it carries.
+    pub fn move_to(&mut self, position: Position) {
+        self.position = position;
+        for cargo in &mut self.cargo {
+            cargo.move_to(position);
+        }
+    }
+
+    /// Whether the unit can be carried by the transport. Only ground units
+    /// that are not structures or transports themselves can board, and
+    /// only infantry can shelter.
+    pub fn can_board(&self, transport: &Unit) -> bool {
+        self.team == transport.team
+            && self.space == Space::Ground
+            && self.capacity == 0
+            && self.class != UnitClass::Structure
+            && (self.class == UnitClass::Infantry || !transport.has_special(Special::Shelter))
+            && (transport.cargo.len() as u32) < transport.capacity
+    }
+}
@@ -0,0 +1,204 @@
+use std::str::{FromStr};
+
+use crate::{Game, Position, Team, DamageAtPos, Metric, Topology};
+
+/// The area covered by a blast.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BlastShape {
+    /// Every tile within the radius in steps.
+    Diamond,
+
+    /// Every tile within the radius in any direction, corners included.
+    Square,
+
+    /// The tiles in a straight line from the centre along each of the
+    /// grid's axes.
+    Cross
+}
+
+impl FromStr for BlastShape {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Diamond" => Ok(BlastShape::Diamond),
+            "Square"  => Ok(BlastShape::Square),
+            "Cross"   => Ok(BlastShape::Cross),
+            _         => Err(())
+        }
+    }
+}
+
+/// How a unit with the Explode special damages the area around its blast
+/// centre. The centre itself is not part of the blast.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct ExplosionProfile {
+    pub radius:  u32,
+    pub shape:   BlastShape,
+
+    /// The damage lost for every step beyond the first away from the
+    /// centre.
+    pub falloff: u32,
+
+    /// Whether walls shelter the tiles behind them from the blast. Walls
+    /// themselves are never damaged.
+    pub blocked_by_walls: bool,
+
+    /// Whether the blast damages units of the exploding unit's team.
+    pub friendly_fire:    bool
+}
+
+impl Default for ExplosionProfile {
+    fn default() -> Self {
+        ExplosionProfile {
+            radius:  2,
+            shape:   BlastShape::Diamond,
+            falloff: 0,
+
+            blocked_by_walls: false,
+            friendly_fire:    true
+        }
+    }
+}
+
+impl ExplosionProfile {
+    /// The tiles the blast reaches and the damage dealt on each, for a
+    /// unit of the team dealing `damage` at the centre.
+    pub fn blast(&self, game: &Game, team: Team, damage: u32, at: Position) -> Vec<DamageAtPos> {
+        let mut blast = Vec::new();
+        for position in at.area(self.radius, Metric::Chebyshev) {
+            let steps = match self.steps(game.board.topology(), at, position) {
+                Some(steps) if steps != 0 => steps,
+                _                         => continue
+            };
+
+            let tile = match game.board.tile_at(position) {
+                Some(tile) => tile,
+                None       => continue
+            };
+
+            if tile.is_wall() || (self.blocked_by_walls && !game.board.has_line_of_sight(at, position)) {
+                continue;
+            }
+
+            if !self.friendly_fire {
+                let friendly = game.board
+                    .entity_at(position)
+                    .and_then(|entity| game.units.get(entity))
+                    .is_some_and(|unit| unit.team == team);
+
+                if friendly {
+                    continue;
+                }
+            }
+
+            let amount = damage.saturating_sub(self.falloff * (steps - 1));
+            if amount != 0 {
+                blast.push(DamageAtPos::new(position, amount));
+            }
+        }
+
+        blast
+    }
+
+    /// How far the tile is from the centre as the shape measures it, or
+    /// None if the blast does not reach it.
+    fn steps(&self, topology: Topology, at: Position, position: Position) -> Option<u32> {
+        let offset  = position - at;
+        let on_axis = offset.x == 0 || offset.y == 0 || (topology == Topology::Hex && offset.x == -offset.y);
+
+        let steps = match self.shape {
+            BlastShape::Diamond          => topology.distance(at, position),
+            BlastShape::Square           => at.chebyshev_distance(&position),
+            BlastShape::Cross if on_axis => topology.distance(at, position),
+            BlastShape::Cross            => return None
+        };
+
+        if steps <= self.radius {
+            Some(steps)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, SpawnData, TileKind, UnitKind};
+
+    fn profile(shape: BlastShape) -> ExplosionProfile {
+        ExplosionProfile { shape, ..ExplosionProfile::default() }
+    }
+
+    fn hits(blast: &[DamageAtPos], x: i32, y: i32) -> Option<u32> {
+        blast.iter().find(|damage| damage.at == Position::new(x, y)).map(|damage| damage.amount)
+    }
+
+    #[test]
+    fn shapes_cover_their_tiles() {
+        let game   = Game::new(Board::new(Dimension::new(9, 9)));
+        let centre = Position::new(4, 4);
+
+        let diamond = profile(BlastShape::Diamond).blast(&game, Team::Red, 5, centre);
+        let square  = profile(BlastShape::Square).blast(&game, Team::Red, 5, centre);
+        let cross   = profile(BlastShape::Cross).blast(&game, Team::Red, 5, centre);
+
+        assert_eq!(diamond.len(), 12);
+        assert_eq!(square.len(), 24);
+        assert_eq!(cross.len(), 8);
+
+        assert_eq!(hits(&diamond, 5, 5), Some(5));
+        assert_eq!(hits(&diamond, 6, 5), None);
+        assert_eq!(hits(&square, 6, 6), Some(5));
+        assert_eq!(hits(&cross, 6, 4), Some(5));
+        assert_eq!(hits(&cross, 5, 5), None);
+        assert_eq!(hits(&diamond, 4, 4), None);
+    }
+
+    #[test]
+    fn damage_falls_off_with_distance() {
+        let game      = Game::new(Board::new(Dimension::new(9, 9)));
+        let explosion = ExplosionProfile { falloff: 3, ..profile(BlastShape::Diamond) };
+        let blast     = explosion.blast(&game, Team::Red, 4, Position::new(4, 4));
+
+        assert_eq!(hits(&blast, 5, 4), Some(4));
+        assert_eq!(hits(&blast, 6, 4), Some(1));
+        assert_eq!(hits(&blast, 5, 5), Some(1));
+
+        let spent = ExplosionProfile { falloff: 4, ..explosion }.blast(&game, Team::Red, 4, Position::new(4, 4));
+        assert_eq!(spent.len(), 4);
+    }
+
+    #[test]
+    fn walls_shelter_the_tiles_behind_them() {
+        let mut game = Game::new(Board::new(Dimension::new(9, 9)));
+        game.board.set_tile_at(Position::new(5, 4), TileKind::Wall);
+
+        let open    = profile(BlastShape::Cross).blast(&game, Team::Red, 5, Position::new(4, 4));
+        let blocked = ExplosionProfile { blocked_by_walls: true, ..profile(BlastShape::Cross) }
+            .blast(&game, Team::Red, 5, Position::new(4, 4));
+
+        assert_eq!(hits(&open, 5, 4), None);
+        assert_eq!(hits(&open, 6, 4), Some(5));
+        assert_eq!(hits(&blocked, 5, 4), None);
+        assert_eq!(hits(&blocked, 6, 4), None);
+        assert_eq!(hits(&blocked, 2, 4), Some(5));
+    }
+
+    #[test]
+    fn friendly_fire_can_be_turned_off() {
+        let mut game = Game::new(Board::new(Dimension::new(9, 9)));
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Red,  Position::new(4, 5))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Blue, Position::new(5, 4))).unwrap();
+
+        let careful = ExplosionProfile { friendly_fire: false, ..profile(BlastShape::Diamond) };
+        let blast   = careful.blast(&game, Team::Red, 5, Position::new(4, 4));
+        assert_eq!(hits(&blast, 4, 5), None);
+        assert_eq!(hits(&blast, 5, 4), Some(5));
+        assert_eq!(hits(&blast, 3, 4), Some(5));
+
+        let reckless = profile(BlastShape::Diamond).blast(&game, Team::Red, 5, Position::new(4, 4));
+        assert_eq!(hits(&reckless, 4, 5), Some(5));
+    }
+}
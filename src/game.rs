@@ -0,0 +1,1273 @@
+use std::collections::{HashMap};
+
+use generational_arena::Arena;
+
+use crate::{EntityIndex, Board, Position, ActionCircle, Occupancy, Definitions, History, Replay, Command, Visibility, AbilityKind, Control};
+use crate::entity::*;
+use crate::victory::*;
+use crate::combat::*;
+use crate::transport::*;
+use crate::healing::*;
+
+/// The funds a team receives at the start of each of its turns.
+pub const INCOME_PER_TURN: u32 = 200;
+
+/// Optional rules that change how the game plays.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    /// Whether units can move through tiles held by their own team. Enemy
+    /// units always block movement.
+    pub pass_through_friendlies: bool,
+
+    /// Whether teams only see the parts of the board within sight of
+    /// their units.
+    pub fog_of_war: bool,
+
+    /// Whether defenders that survive an attack strike back at attackers
+    /// within their own range.
+    pub counterattacks: bool,
+
+    /// Whether units aboard a destroyed transport get out onto the tiles
+    /// around it. When this is off, or there is no room, they are lost.
+    pub eject_cargo: bool
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            pass_through_friendlies: true,
+            fog_of_war:              true,
+            counterattacks:          true,
+            eject_cargo:             true
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Game {
+    pub player:       Team,
+    pub turn:         u32,
+    pub outcome:      Outcome,
+    pub board:        Board,
+    pub funds:        HashMap<Team, u32>,
+    pub definitions:  Definitions,
+    pub rules:        Rules,
+
+    /// Who gives the orders for each team. Teams that are missing are
+    /// played by humans.
+    pub controls:     HashMap<Team, Control>,
+
+    pub units:         Arena<Unit>,
+
+    /// The id given to the next unit that is spawned.
+    pub next_id:       u32,
+
+    pub damage_queue:  Vec<DamageAtPos>,
+    pub healing_queue: Vec<HealingAtPos>,
+    pub history:       History,
+    pub visibility:    HashMap<Team, Visibility>,
+
+    /// Every command applied to the game, if it is being recorded.
+    pub recording:     Option<Replay>,
+
+    pub victory_conditions: Vec<Box<dyn VictoryCondition>>
+}
+
+#[derive(Debug, Clone)]
+pub struct SpawnData {
+    pub kind:     UnitKind,
+    pub team:     Team,
+    pub position: Position
+}
+
+impl SpawnData {
+    pub fn new(kind: UnitKind, team: Team, position: Position) -> Self {
+        SpawnData {
+            kind,
+            team,
+            position
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SpawnError {
+    KindUndefined,
+    TerrainIncompatible,
+    PositionOutOfBounds,
+    PositionOccupied
+}
+
+impl Game {
+    pub fn new(board: Board) -> Self {
+        Game {
+            player:       Team::White,
+            turn:         0,
+            outcome:      Outcome::Undecided,
+            board,
+            funds:        HashMap::new(),
+            definitions:  Definitions::default(),
+            rules:        Rules::default(),
+            controls:     HashMap::new(),
+            units:         Arena::new(),
+            next_id:       0,
+            damage_queue:  Vec::new(),
+            healing_queue: Vec::new(),
+            history:       History::default(),
+            visibility:    HashMap::new(),
+            recording:     None,
+
+            victory_conditions: Vec::new()
+        }
+    }
+
+    /// The entity index of the unit with the id, if it is on the board.
+    pub fn entity_with_id(&self, id: u32) -> Option<EntityIndex> {
+        self.units
+            .iter()
+            .find(|(_, unit)| unit.id == id)
+            .map(|(entity, _)| entity)
+    }
+
+    /// Adds the command to the recording, if there is one.
+    pub fn record(&mut self, command: Command) {
+        if let Some(recording) = &mut self.recording {
+            recording.commands.push(command);
+        }
+    }
+
+    pub fn spawn(&mut self, data: SpawnData) -> Result<EntityIndex, SpawnError> {
+        if !self.board.in_bounds(data.position) {
+            return Err(SpawnError::PositionOutOfBounds);
+        }
+
+        if self.board.entity_at(data.position).is_some() {
+            return Err(SpawnError::PositionOccupied);
+        }
+
+        let definition = self.definitions.get(&data.kind).ok_or(SpawnError::KindUndefined)?;
+
+        let mut unit = Unit::new(definition, data.team, data.position);
+        unit.id      = self.next_id;
+        self.next_id += 1;
+
+        let entity = self.units.insert(unit);
+
+        self.board.insert_at(data.position, entity);
+        self.update_visibility();
+
+        Ok(entity)
+    }
+
+    /// Recomputes what each team can see. Call this whenever units move,
+    /// appear or disappear.
+    pub fn update_visibility(&mut self) {
+        let size = self.board.size();
+        for (_, unit) in &self.units {
+            self.visibility
+                .entry(unit.team)
+                .or_insert_with(|| Visibility::new(size));
+        }
+
+        let metric = self.board.topology().metric();
+        for (team, visibility) in &mut self.visibility {
+            let eyes = self.units
+                .iter()
+                .filter(|(_, unit)| unit.team == *team)
+                .map(|(_, unit)| (unit.position, unit.sight));
+
+            visibility.update(eyes, metric);
+        }
+    }
+
+    /// Whether the team can currently see what stands on the tile.
+    pub fn can_see(&self, team: Team, position: Position) -> bool {
+        if !self.rules.fog_of_war {
+            return self.board.in_bounds(position);
+        }
+
+        self.visibility
+            .get(&team)
+            .is_some_and(|visibility| visibility.is_visible(position))
+    }
+
+    /// Whether the team knows the terrain of the tile, either because it
+    /// can see it or because it has seen it before.
+    pub fn has_explored(&self, team: Team, position: Position) -> bool {
+        if !self.rules.fog_of_war {
+            return self.board.in_bounds(position);
+        }
+
+        self.visibility
+            .get(&team)
+            .is_some_and(|visibility| visibility.is_explored(position))
+    }
+
+    /// How a unit of the team is affected by whatever stands on the tile,
+    /// as far as the team knows. Units hidden by the fog of war are not
+    /// known about until they are bumped into.
+    pub fn occupancy(&self, team: Team, position: Position) -> Occupancy {
+        let other = self.board
+            .entity_at(position)
+            .and_then(|other| self.units.get(other))
+            .filter(|other| other.team == team || self.can_see(team, other.position));
+
+        match other {
+            None => Occupancy::Free,
+
+            Some(other) if other.team == team && self.rules.pass_through_friendlies => {
+                Occupancy::PassThrough
+            },
+
+            Some(_) => Occupancy::Blocked
+        }
+    }
+
+    /// The tiles the unit can move to with its remaining actions, taking
+    /// the other units its team can see into account.
+    pub fn movement_range(&self, entity: EntityIndex) -> Option<ActionCircle> {
+        let unit = self.units.get(entity)?;
+
+        Some(ActionCircle::with_occupancy(
+            unit.position,
+            unit.actions,
+            Some(unit.space),
+            &self.board,
+            |position| self.occupancy(unit.team, position)
+        ))
+    }
+
+    /// The tiles the unit can fire at: those within its range that have a
+    /// clear line of sight from its position.
+    pub fn attack_range(&self, entity: EntityIndex) -> Option<Vec<Position>> {
+        let unit = self.units.get(entity)?;
+
+        let positions = unit.position
+            .area(unit.range, self.board.topology().metric())
+            .filter(|position| *position != unit.position && self.board.in_bounds(*position))
+            .filter(|position| self.board.has_line_of_sight(unit.position, *position))
+            .collect();
+
+        Some(positions)
+    }
+
+    /// The shortest route for the unit that visits each waypoint in turn
+    /// before arriving at the destination, excluding the unit's position.
+    /// Returns None if the route cannot be walked with the unit's
+    /// remaining actions.
+    pub fn route(&self, entity: EntityIndex, waypoints: &[Position], to: Position) -> Option<Vec<Position>> {
+        let unit = self.units.get(entity)?;
+
+        let mut path   = Vec::new();
+        let mut from   = unit.position;
+        let mut budget = unit.actions;
+
+        for target in waypoints.iter().chain(std::iter::once(&to)) {
+            let action_circle = ActionCircle::with_occupancy(
+                from,
+                budget,
+                Some(unit.space),
+                &self.board,
+                |position| self.occupancy(unit.team, position)
+            );
+
+            let segment = action_circle.path_to(*target)?;
+
+            budget -= segment.len() as u32;
+            from    = *target;
+            path.extend(segment);
+        }
+
+        Some(path)
+    }
+
+    /// The funds available to the team.
+    pub fn funds(&self, team: Team) -> u32 {
+        self.funds.get(&team).copied().unwrap_or(0)
+    }
+
+    /// Removes the amount from the team's funds, returning false if the
+    /// team cannot afford it.
+    pub fn spend(&mut self, team: Team, amount: u32) -> bool {
+        let funds = self.funds.entry(team).or_insert(0);
+        if *funds < amount {
+            return false;
+        }
+
+        *funds -= amount;
+
+        true
+    }
+
+    /// Passes control to the next team with units on the board. Returns
+    /// false if the game is over, either because no other team remains or
+    /// because a victory condition was met.
+    pub fn next_turn(&mut self) -> bool {
+        if self.outcome != Outcome::Undecided {
+            return false;
+        }
+
+        let current_team  = self.player;
+        let mut next_team = get_next_team(current_team);
+
+        shelter_infantry(self, current_team);
+
+        let mut next_turn_valid = false;
+
+        while !next_turn_valid && current_team != next_team {
+            for (_, unit) in &self.units {
+                if unit.team == next_team {
+                    next_turn_valid = true;
+                    break;
+                }
+            }
+
+            if !next_turn_valid {
+                next_team = get_next_team(next_team);
+            }
+        }
+
+        if !next_turn_valid {
+            let survivor = self.units.iter().any(|(_, unit)| unit.team == current_team);
+            self.outcome = if survivor {
+                Outcome::Winner(current_team)
+            } else {
+                Outcome::Draw
+            };
+
+            return false;
+        }
+
+        self.player = next_team;
+        self.turn  += 1;
+
+        self.history.clear();
+
+        *self.funds.entry(self.player).or_insert(0) += INCOME_PER_TURN;
+
+        for (_, unit) in &mut self.units {
+            if unit.team == self.player {
+                unit.actions = unit.actions_max;
+            }
+        }
+
+        self.refuel(self.player);
+
+        self.check_victory() == Outcome::Undecided
+    }
+
+    /// Refuels the team's aircraft that are next to one of its Airbases or
+    /// carriers and drains the fuel of the others. Aircraft that run out
+    /// of fuel crash.
+    fn refuel(&mut self, team: Team) {
+        let tankers: Vec<Position> = self.units
+            .iter()
+            .filter(|(_, unit)| unit.team == team && unit.has_special(Special::Refuel))
+            .map(|(_, unit)| unit.position)
+            .collect();
+
+        let topology    = self.board.topology();
+        let mut crashed = Vec::new();
+        for (entity, unit) in &mut self.units {
+            if unit.team != team || !unit.uses_fuel() {
+                continue;
+            }
+
+            if tankers.iter().any(|tanker| topology.distance(*tanker, unit.position) <= 1) {
+                unit.fuel = unit.fuel_max;
+            } else {
+                unit.fuel = unit.fuel.saturating_sub(1);
+                if unit.fuel == 0 {
+                    crashed.push(entity);
+                }
+            }
+        }
+
+        for entity in crashed {
+            if let Some(unit) = self.units.remove(entity) {
+                self.board.remove_at(unit.position);
+            }
+        }
+
+        self.update_visibility();
+    }
+
+    /// Evaluates the victory conditions, eliminating teams as required,
+    /// and records the outcome of the game once it has been decided.
+    pub fn check_victory(&mut self) -> Outcome {
+        if self.outcome != Outcome::Undecided {
+            return self.outcome;
+        }
+
+        let mut conditions = std::mem::take(&mut self.victory_conditions);
+        for condition in conditions.iter_mut() {
+            match condition.evaluate(self) {
+                Verdict::Continue => {
+
+                },
+
+                Verdict::Eliminate(teams, elimination) => {
+                    for team in teams {
+                        self.eliminate(team, elimination);
+                    }
+                },
+
+                Verdict::Winner(team) => {
+                    self.outcome = Outcome::Winner(team);
+                    break;
+                },
+
+                Verdict::Draw => {
+                    self.outcome = Outcome::Draw;
+                    break;
+                }
+            }
+        }
+
+        self.victory_conditions = conditions;
+
+        if self.outcome == Outcome::Undecided {
+            let mut teams = self.units.iter().map(|(_, unit)| unit.team);
+            if let Some(first) = teams.next() {
+                if teams.all(|team| team == first) {
+                    self.outcome = Outcome::Winner(first);
+                }
+            } else {
+                self.outcome = Outcome::Draw;
+            }
+        }
+
+        self.outcome
+    }
+
+    fn eliminate(&mut self, team: Team, elimination: Elimination) {
+        match elimination {
+            Elimination::Remove => {
+                for (_, unit) in &self.units {
+                    if unit.team == team {
+                        self.board.remove_at(unit.position);
+                    }
+                }
+
+                self.units.retain(|_, unit| unit.team != team);
+            },
+
+            Elimination::Convert => {
+                let captor = self.player;
+                for (_, unit) in &mut self.units {
+                    if unit.team == team {
+                        unit.team    = captor;
+                        unit.actions = 0;
+
+                        for cargo in &mut unit.cargo {
+                            cargo.team = captor;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToMove {
+    pub entity: EntityIndex,
+    pub to:     Position,
+
+    /// The exact steps to take, excluding the unit's position. The
+    /// shortest route is taken when no path is given.
+    pub path:   Option<Vec<Position>>
+}
+
+#[derive(Debug)]
+pub enum MoveError {
+    UnitInvalid,
+    UnitExhausted,
+    TerrainIncompatible,
+    DestinationOccupied,
+    DestinationUnreachable,
+    PathInvalid,
+    PathBlocked
+}
+
+/// Checks that the move is allowed, returning the actions it costs.
+pub fn validate_move(game: &Game, intent: &IntentToMove) -> Result<u32, MoveError> {
+    let action_circle = game.movement_range(intent.entity).ok_or(MoveError::UnitInvalid)?;
+    let unit = game.units.get(intent.entity).unwrap();
+
+    if unit.actions == 0 {
+        return Err(MoveError::UnitExhausted);
+    }
+
+    if game.occupancy(unit.team, intent.to) != Occupancy::Free {
+        return Err(MoveError::DestinationOccupied);
+    }
+
+    let cost = match &intent.path {
+        None => {
+            if !action_circle.contains(intent.to) {
+                return Err(MoveError::DestinationUnreachable);
+            }
+
+            let tile = game.board.tile_at(intent.to).unwrap();
+            if !unit.space.can_traverse(tile.traverse()) {
+                return Err(MoveError::TerrainIncompatible);
+            }
+
+            action_circle.cost_to(intent.to).unwrap()
+        },
+
+        Some(path) => {
+            if path.last() != Some(&intent.to) {
+                return Err(MoveError::PathInvalid);
+            }
+
+            if path.len() as u32 > unit.actions {
+                return Err(MoveError::DestinationUnreachable);
+            }
+
+            let mut previous = unit.position;
+            for step in path {
+                if game.board.distance(previous, *step) != 1 || !game.board.in_bounds(*step) {
+                    return Err(MoveError::PathInvalid);
+                }
+
+                let tile = game.board.tile_at(*step).unwrap();
+                if !unit.space.can_traverse(tile.traverse()) {
+                    return Err(MoveError::TerrainIncompatible);
+                }
+
+                if game.occupancy(unit.team, *step) == Occupancy::Blocked {
+                    return Err(MoveError::PathBlocked);
+                }
+
+                previous = *step;
+            }
+
+            path.len() as u32
+        }
+    };
+
+    Ok(cost)
+}
+
+/// Moves the unit along its path. A unit that runs into a unit hidden by
+/// the fog of war stops on the last free tile before it and loses the
+/// rest of its actions.
+pub fn move_unit(game: &mut Game, intent: IntentToMove) -> Result<(), MoveError> {
+    let cost = validate_move(game, &intent)?;
+    let path = match intent.path {
+        Some(path) => path,
+        None       => game.movement_range(intent.entity).unwrap().path_to(intent.to).unwrap()
+    };
+
+    let unit = &game.units[intent.entity];
+
+    let mut to     = unit.position;
+    let mut bumped = false;
+    for step in path {
+        match game.board.entity_at(step).and_then(|other| game.units.get(other)) {
+            None => {
+                to = step;
+            },
+
+            Some(other) if other.team == unit.team && game.rules.pass_through_friendlies => {
+
+            },
+
+            Some(_) => {
+                bumped = true;
+                break;
+            }
+        }
+    }
+
+    let unit = game.units.get_mut(intent.entity).unwrap();
+
+    if to != unit.position {
+        game.board.swap_between(unit.position, to);
+        unit.move_to(to);
+    }
+
+    if bumped {
+        unit.actions = 0;
+    } else {
+        unit.actions -= cost;
+    }
+
+    game.update_visibility();
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToAttack {
+    pub entity:        EntityIndex,
+    pub target_entity: EntityIndex
+}
+
+#[derive(Debug)]
+pub enum AttackError {
+    UnitInvalid,
+    UnitExhausted,
+    TargetInvalid,
+    TargetFriendly,
+    TargetOutOfRange,
+    TargetHidden,
+    TargetNotVisible,
+    TargetAirborne
+}
+
+#[derive(Debug, Clone)]
+pub struct DamageAtPos {
+    pub at:     Position,
+    pub amount: u32
+}
+
+impl DamageAtPos {
+    pub fn new(at: Position, amount: u32) -> Self {
+        DamageAtPos {
+            at,
+            amount
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealingAtPos {
+    pub at:     Position,
+    pub amount: u32,
+
+    /// The place in the hold of the unit on the tile, if the healing is
+    /// for its cargo rather than the unit itself.
+    pub cargo:  Option<usize>
+}
+
+impl HealingAtPos {
+    pub fn new(at: Position, amount: u32) -> Self {
+        HealingAtPos {
+            at,
+            amount,
+            cargo: None
+        }
+    }
+
+    pub fn aboard(at: Position, cargo: usize, amount: u32) -> Self {
+        HealingAtPos {
+            at,
+            amount,
+            cargo: Some(cargo)
+        }
+    }
+}
+
+/// Checks that the attack is allowed.
+pub fn validate_attack(game: &Game, intent: &IntentToAttack) -> Result<(), AttackError> {
+    if intent.entity == intent.target_entity {
+        return Err(AttackError::TargetFriendly);
+    }
+
+    let unit   = game.units.get(intent.entity).ok_or(AttackError::UnitInvalid)?;
+    let target = game.units.get(intent.target_entity).ok_or(AttackError::TargetInvalid)?;
+
+    let position        = unit.position;
+    let target_position = target.position;
+
+    // Units hidden by the fog of war cannot be targeted.
+    if !game.can_see(unit.team, target_position) {
+        return Err(AttackError::TargetHidden);
+    }
+
+    if game.board.distance(position, target_position) > unit.range {
+        return Err(AttackError::TargetOutOfRange);
+    }
+
+    if !game.board.has_line_of_sight(position, target_position) {
+        return Err(AttackError::TargetNotVisible);
+    }
+
+    if unit.team == target.team {
+        return Err(AttackError::TargetFriendly);
+    }
+
+    if !can_target(unit, target) {
+        return Err(AttackError::TargetAirborne);
+    }
+
+    if unit.actions == 0 {
+        return Err(AttackError::UnitExhausted);
+    }
+
+    Ok(())
+}
+
+pub fn attack_with_unit(game: &mut Game, intent: IntentToAttack) -> Result<(), AttackError> {
+    validate_attack(game, &intent)?;
+
+    let position        = game.units[intent.entity].position;
+    let target_position = game.units[intent.target_entity].position;
+
+    let prediction = predict_attack(game, intent.entity, intent.target_entity).unwrap();
+    let unit       = game.units.get_mut(intent.entity).unwrap();
+
+    game.damage_queue.push(DamageAtPos::new(target_position, prediction.damage));
+
+    if prediction.counter != 0 {
+        game.damage_queue.push(DamageAtPos::new(position, prediction.counter));
+    }
+
+    unit.actions = 0;
+
+    if unit.has_special(Special::Explode) {
+        explode(game, intent.entity, target_position);
+    }
+
+    Ok(())
+}
+
+/// Destroys the unit and damages everything around the blast centre.
+fn explode(game: &mut Game, entity: EntityIndex, at: Position) {
+    let unit = game.units.get_mut(entity).unwrap();
+    unit.health = 0;
+
+    let (explosion, team, damage) = (unit.explosion, unit.team, unit.damage);
+
+    let blast = explosion.blast(game, team, damage, at);
+    game.damage_queue.extend(blast);
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToDetonate {
+    pub entity: EntityIndex
+}
+
+#[derive(Debug)]
+pub enum DetonateError {
+    UnitInvalid,
+    UnitExhausted,
+    UnitCannotExplode
+}
+
+/// Checks that the unit can blow itself up.
+pub fn validate_detonate(game: &Game, intent: &IntentToDetonate) -> Result<(), DetonateError> {
+    let unit = game.units.get(intent.entity).ok_or(DetonateError::UnitInvalid)?;
+
+    if !unit.has_special(Special::Explode) {
+        return Err(DetonateError::UnitCannotExplode);
+    }
+
+    if unit.actions == 0 {
+        return Err(DetonateError::UnitExhausted);
+    }
+
+    Ok(())
+}
+
+/// Blows the unit up where it stands, damaging the area around it.
+pub fn detonate_unit(game: &mut Game, intent: IntentToDetonate) -> Result<(), DetonateError> {
+    validate_detonate(game, &intent)?;
+
+    let position = game.units[intent.entity].position;
+    game.units[intent.entity].actions = 0;
+    explode(game, intent.entity, position);
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToBuild {
+    pub entity: EntityIndex,
+    pub kind:   UnitKind,
+    pub at:     Position
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    UnitInvalid,
+    UnitExhausted,
+    UnitCannotBuild,
+    StructureInvalid,
+    TerrainIncompatible,
+    PositionOutOfRange,
+    PositionOccupied,
+    PositionNotCoastal,
+    InsufficientFunds
+}
+
+/// Checks that the structure can be built.
+pub fn validate_build(game: &Game, intent: &IntentToBuild) -> Result<(), BuildError> {
+    let unit = game.units.get(intent.entity).ok_or(BuildError::UnitInvalid)?;
+
+    if !unit.has_special(Special::Build) {
+        return Err(BuildError::UnitCannotBuild);
+    }
+
+    let structure = game.definitions.get(&intent.kind).ok_or(BuildError::StructureInvalid)?;
+    let cost      = structure.build_cost().ok_or(BuildError::StructureInvalid)?;
+    if unit.actions < cost {
+        return Err(BuildError::UnitExhausted);
+    }
+
+    if game.board.distance(unit.position, intent.at) != 1 {
+        return Err(BuildError::PositionOutOfRange);
+    }
+
+    let tile = game.board.tile_at(intent.at).ok_or(BuildError::PositionOutOfRange)?;
+    if !Space::Ground.can_traverse(tile.traverse()) {
+        return Err(BuildError::TerrainIncompatible);
+    }
+
+    if game.board.entity_at(intent.at).is_some() {
+        return Err(BuildError::PositionOccupied);
+    }
+
+    if structure.has_special(Special::Coastal) && !game.board.is_coastal(intent.at) {
+        return Err(BuildError::PositionNotCoastal);
+    }
+
+    let team          = unit.team;
+    let cost_in_funds = structure.cost;
+    if game.funds(team) < cost_in_funds {
+        return Err(BuildError::InsufficientFunds);
+    }
+
+    Ok(())
+}
+
+pub fn build_with_unit(game: &mut Game, intent: IntentToBuild) -> Result<EntityIndex, BuildError> {
+    validate_build(game, &intent)?;
+
+    let team          = game.units[intent.entity].team;
+    let definition    = game.definitions.get(&intent.kind).unwrap();
+    let cost          = definition.build_cost().unwrap();
+    let cost_in_funds = definition.cost;
+
+    let structure = game.spawn(SpawnData::new(intent.kind, team, intent.at))
+        .map_err(|_| BuildError::PositionOccupied)?;
+
+    game.spend(team, cost_in_funds);
+
+    // A structure cannot act on the turn it was built.
+    game.units[structure].actions = 0;
+    game.units[intent.entity].actions -= cost;
+
+    Ok(structure)
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToProduce {
+    pub entity: EntityIndex,
+    pub kind:   UnitKind,
+    pub at:     Position
+}
+
+#[derive(Debug)]
+pub enum ProduceError {
+    UnitInvalid,
+    UnitExhausted,
+    UnitNotInRoster,
+    TerrainIncompatible,
+    PositionOutOfRange,
+    PositionOccupied,
+    InsufficientFunds
+}
+
+/// Checks that the structure can produce the unit.
+pub fn validate_produce(game: &Game, intent: &IntentToProduce) -> Result<(), ProduceError> {
+    let structure = game.units.get(intent.entity).ok_or(ProduceError::UnitInvalid)?;
+
+    let roster = game.definitions.get(&structure.kind).map_or(&[][..], |definition| &definition.roster);
+    if !roster.contains(&intent.kind) {
+        return Err(ProduceError::UnitNotInRoster);
+    }
+
+    if structure.actions == 0 {
+        return Err(ProduceError::UnitExhausted);
+    }
+
+    if game.board.distance(structure.position, intent.at) != 1 {
+        return Err(ProduceError::PositionOutOfRange);
+    }
+
+    let team       = structure.team;
+    let definition = game.definitions.get(&intent.kind).ok_or(ProduceError::UnitNotInRoster)?;
+    let cost       = definition.cost;
+
+    let tile = game.board.tile_at(intent.at).ok_or(ProduceError::PositionOutOfRange)?;
+    if !definition.space.can_traverse(tile.traverse()) {
+        return Err(ProduceError::TerrainIncompatible);
+    }
+
+    if game.board.entity_at(intent.at).is_some() {
+        return Err(ProduceError::PositionOccupied);
+    }
+
+    if game.funds(team) < cost {
+        return Err(ProduceError::InsufficientFunds);
+    }
+
+    Ok(())
+}
+
+pub fn produce_with_unit(game: &mut Game, intent: IntentToProduce) -> Result<EntityIndex, ProduceError> {
+    validate_produce(game, &intent)?;
+
+    let team = game.units[intent.entity].team;
+    let cost = game.definitions.get(&intent.kind).unwrap().cost;
+
+    let produced = game.spawn(SpawnData::new(intent.kind, team, intent.at))
+        .map_err(|_| ProduceError::PositionOccupied)?;
+
+    game.spend(team, cost);
+
+    // A unit cannot act on the turn it was produced.
+    game.units[produced].actions = 0;
+    game.units[intent.entity].actions -= 1;
+
+    Ok(produced)
+}
+
+/// Any order a player can give, used by computer players and other callers
+/// that drive the game without the tcod front end.
+#[derive(Debug, Clone)]
+pub enum Intent {
+    Move(IntentToMove),
+    Attack(IntentToAttack),
+    Build(IntentToBuild),
+    Produce(IntentToProduce),
+    Embark(IntentToEmbark),
+    Disembark(IntentToDisembark),
+    Heal(IntentToHeal),
+    Detonate(IntentToDetonate),
+    EndTurn
+}
+
+impl Intent {
+    /// The unit giving the order, if any.
+    pub fn entity(&self) -> Option<EntityIndex> {
+        match self {
+            Intent::Move(intent)      => Some(intent.entity),
+            Intent::Attack(intent)    => Some(intent.entity),
+            Intent::Build(intent)     => Some(intent.entity),
+            Intent::Produce(intent)   => Some(intent.entity),
+            Intent::Embark(intent)    => Some(intent.entity),
+            Intent::Disembark(intent) => Some(intent.entity),
+            Intent::Heal(intent)      => Some(intent.entity),
+            Intent::Detonate(intent)  => Some(intent.entity),
+            Intent::EndTurn           => None
+        }
+    }
+
+    /// The ability the unit needs to carry out the order, if any.
+    pub fn ability(&self) -> Option<AbilityKind> {
+        match self {
+            Intent::Move(_)      => Some(AbilityKind::Move),
+            Intent::Attack(_)    => Some(AbilityKind::Attack),
+            Intent::Build(_)     => Some(AbilityKind::Build),
+            Intent::Produce(_)   => Some(AbilityKind::Produce),
+            Intent::Embark(_)    => Some(AbilityKind::Embark),
+            Intent::Disembark(_) => Some(AbilityKind::Disembark),
+            Intent::Heal(_)      => Some(AbilityKind::Heal),
+            Intent::Detonate(_)  => Some(AbilityKind::Detonate),
+            Intent::EndTurn      => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IntentError {
+    Move(MoveError),
+    Attack(AttackError),
+    Build(BuildError),
+    Produce(ProduceError),
+    Embark(EmbarkError),
+    Disembark(DisembarkError),
+    Heal(HealError),
+    Detonate(DetonateError),
+
+    /// The unit's kind does not have the ability the intent needs.
+    AbilityUnavailable(AbilityKind),
+
+    /// The unit belongs to a team whose turn it is not.
+    NotYourUnit,
+    GameOver
+}
+
+/// Applies the queued damage and removes any units that were destroyed,
+/// then heals the survivors. Intents applied before the queues were
+/// resolved can no longer be undone.
+pub fn bring_out_your_dead(game: &mut Game) {
+    if !game.damage_queue.is_empty() || !game.healing_queue.is_empty() {
+        game.history.seal();
+        game.record(Command::Resolve);
+    }
+
+    for damage in &game.damage_queue {
+        if let Some(entity) = game.board.entity_at(damage.at) {
+            if let Some(unit) = game.units.get_mut(entity) {
+                unit.health -= damage.amount.min(unit.health);
+            }
+        }
+    }
+
+    game.damage_queue.clear();
+
+    for healing in &game.healing_queue {
+        if let Some(entity) = game.board.entity_at(healing.at) {
+            let patient = game.units.get_mut(entity).and_then(|unit| match healing.cargo {
+                Some(cargo) => unit.cargo.get_mut(cargo),
+                None        => Some(unit)
+            });
+
+            if let Some(patient) = patient {
+                patient.health = (patient.health + healing.amount).min(patient.health_max);
+            }
+        }
+    }
+
+    game.healing_queue.clear();
+
+    let wrecks: Vec<(Vec<Unit>, Position)> = game.units
+        .iter_mut()
+        .filter(|(_, unit)| unit.health == 0 && !unit.cargo.is_empty())
+        .map(|(_, unit)| (std::mem::take(&mut unit.cargo), unit.position))
+        .collect();
+
+    // Clears the tiles of every destroyed unit, including those that blew
+    // themselves up rather than being damaged.
+    for (entity, unit) in &game.units {
+        if unit.health == 0 && game.board.entity_at(unit.position) == Some(entity) {
+            game.board.remove_at(unit.position);
+        }
+    }
+
+    game.units.retain(|_, unit| {
+        unit.health != 0
+    });
+
+    if game.rules.eject_cargo {
+        for (cargo, at) in wrecks {
+            eject_cargo(game, cargo, at);
+        }
+    }
+
+    game.update_visibility();
+
+    game.check_victory();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Dimension, TileKind};
+
+    #[test]
+    fn engineers_build_structures_next_to_them() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let engineer = game.spawn(SpawnData::new(UnitKind::ENGINEER, Team::Red, Position::new(2, 2))).unwrap();
+        game.funds.insert(Team::Red, 250);
+
+        let far = build_with_unit(&mut game, IntentToBuild {
+            entity: engineer,
+            kind:   UnitKind::BARRACKS,
+            at:     Position::new(3, 3)
+        });
+
+        assert!(matches!(far, Err(BuildError::PositionOutOfRange)));
+
+        let barracks = build_with_unit(&mut game, IntentToBuild {
+            entity: engineer,
+            kind:   UnitKind::BARRACKS,
+            at:     Position::new(2, 3)
+        }).unwrap();
+
+        assert_eq!(game.units[barracks].kind, UnitKind::BARRACKS);
+        assert_eq!(game.units[barracks].actions, 0);
+        assert_eq!(game.board.entity_at(Position::new(2, 3)), Some(barracks));
+        assert_eq!(game.units[engineer].actions, 1);
+        assert_eq!(game.funds(Team::Red), 50);
+    }
+
+    #[test]
+    fn only_engineers_build() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK, Team::Red, Position::new(2, 2))).unwrap();
+
+        let result = build_with_unit(&mut game, IntentToBuild {
+            entity: tank,
+            kind:   UnitKind::BARRACKS,
+            at:     Position::new(2, 3)
+        });
+
+        assert!(matches!(result, Err(BuildError::UnitCannotBuild)));
+        assert!(game.board.entity_at(Position::new(2, 3)).is_none());
+    }
+
+    #[test]
+    fn structures_cost_funds() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let engineer = game.spawn(SpawnData::new(UnitKind::ENGINEER, Team::Red, Position::new(2, 2))).unwrap();
+        game.funds.insert(Team::Red, 100);
+
+        let result = build_with_unit(&mut game, IntentToBuild {
+            entity: engineer,
+            kind:   UnitKind::FACTORY,
+            at:     Position::new(2, 3)
+        });
+
+        assert!(matches!(result, Err(BuildError::InsufficientFunds)));
+        assert_eq!(game.funds(Team::Red), 100);
+        assert!(game.board.entity_at(Position::new(2, 3)).is_none());
+    }
+
+    #[test]
+    fn harbors_are_built_on_the_coast() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let engineer = game.spawn(SpawnData::new(UnitKind::ENGINEER, Team::Red, Position::new(2, 2))).unwrap();
+        game.funds.insert(Team::Red, 300);
+
+        let inland = build_with_unit(&mut game, IntentToBuild {
+            entity: engineer,
+            kind:   UnitKind::HARBOR,
+            at:     Position::new(2, 3)
+        });
+
+        assert!(matches!(inland, Err(BuildError::PositionNotCoastal)));
+
+        game.board.set_tile_at(Position::new(2, 4), TileKind::Ocean);
+        let harbor = build_with_unit(&mut game, IntentToBuild {
+            entity: engineer,
+            kind:   UnitKind::HARBOR,
+            at:     Position::new(2, 3)
+        }).unwrap();
+
+        assert_eq!(game.units[harbor].kind, UnitKind::HARBOR);
+        assert_eq!(game.funds(Team::Red), 0);
+    }
+
+    #[test]
+    fn teams_collect_income_when_their_turn_starts() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        game.player  = Team::Red;
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Red,  Position::new(1, 1))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Blue, Position::new(3, 3))).unwrap();
+
+        assert!(game.next_turn());
+        assert_eq!(game.player, Team::Blue);
+        assert_eq!(game.funds(Team::Blue), INCOME_PER_TURN);
+        assert_eq!(game.funds(Team::Red), 0);
+
+        assert!(game.spend(Team::Blue, INCOME_PER_TURN));
+        assert!(!game.spend(Team::Blue, 1));
+    }
+
+    #[test]
+    fn aircraft_refuel_at_airbases_and_crash_without_fuel() {
+        let mut game = Game::new(Board::new(Dimension::new(7, 5)));
+        game.player  = Team::Blue;
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Blue, Position::new(6, 4))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::AIRBASE, Team::Red, Position::new(1, 1))).unwrap();
+
+        let docked = game.spawn(SpawnData::new(UnitKind::FIGHTER, Team::Red, Position::new(1, 2))).unwrap();
+        let stray  = game.spawn(SpawnData::new(UnitKind::FIGHTER, Team::Red, Position::new(5, 2))).unwrap();
+        game.units[docked].fuel = 1;
+        game.units[stray].fuel  = 1;
+
+        assert!(game.next_turn());
+        assert_eq!(game.units[docked].fuel, game.units[docked].fuel_max);
+        assert!(!game.units.contains(stray));
+        assert!(game.board.entity_at(Position::new(5, 2)).is_none());
+    }
+
+    #[test]
+    fn attacks_need_a_clear_line_of_sight() {
+        let mut game = Game::new(Board::new(Dimension::new(7, 5)));
+        game.player  = Team::Red;
+        game.board.set_tile_at(Position::new(3, 2), TileKind::Wall);
+
+        let tank  = game.spawn(SpawnData::new(UnitKind::TANK, Team::Red,  Position::new(2, 2))).unwrap();
+        let enemy = game.spawn(SpawnData::new(UnitKind::TANK, Team::Blue, Position::new(4, 2))).unwrap();
+
+        let blocked = attack_with_unit(&mut game, IntentToAttack { entity: tank, target_entity: enemy });
+        assert!(matches!(blocked, Err(AttackError::TargetNotVisible)));
+        assert!(!game.attack_range(tank).unwrap().contains(&Position::new(4, 2)));
+
+        game.board.set_tile_at(Position::new(3, 2), TileKind::Floor);
+
+        assert!(attack_with_unit(&mut game, IntentToAttack { entity: tank, target_entity: enemy }).is_ok());
+    }
+
+    #[test]
+    fn airbase_produces_aircraft_from_its_roster() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        game.player = Team::Red;
+        game.funds.insert(Team::Red, 1000);
+
+        let airbase = game.spawn(SpawnData::new(UnitKind::AIRBASE, Team::Red, Position::new(2, 2))).unwrap();
+
+        let fighter = produce_with_unit(&mut game, IntentToProduce {
+            entity: airbase,
+            kind:   UnitKind::FIGHTER,
+            at:     Position::new(2, 1)
+        });
+
+        assert_eq!(game.units[fighter.unwrap()].kind, UnitKind::FIGHTER);
+
+        let tank = validate_produce(&game, &IntentToProduce {
+            entity: airbase,
+            kind:   UnitKind::TANK,
+            at:     Position::new(2, 3)
+        });
+
+        assert!(matches!(tank, Err(ProduceError::UnitNotInRoster)));
+    }
+
+    #[test]
+    fn detonated_units_leave_their_tile_empty() {
+        let mut game = Game::new(Board::new(Dimension::new(7, 7)));
+        let missile  = game.spawn(SpawnData::new(UnitKind::MISSILE, Team::Red, Position::new(3, 3))).unwrap();
+
+        detonate_unit(&mut game, IntentToDetonate { entity: missile }).unwrap();
+        bring_out_your_dead(&mut game);
+
+        assert!(!game.units.contains(missile));
+        assert_eq!(game.board.entity_at(Position::new(3, 3)), None);
+    }
+
+    #[test]
+    fn missiles_leave_their_launch_tile_empty() {
+        let mut game = Game::new(Board::new(Dimension::new(7, 7)));
+        game.player  = Team::Red;
+        let missile  = game.spawn(SpawnData::new(UnitKind::MISSILE, Team::Red,  Position::new(1, 3))).unwrap();
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK,    Team::Blue, Position::new(4, 3))).unwrap();
+
+        attack_with_unit(&mut game, IntentToAttack { entity: missile, target_entity: tank }).unwrap();
+        bring_out_your_dead(&mut game);
+
+        assert!(!game.units.contains(missile));
+        assert!(!game.units.contains(tank));
+        assert_eq!(game.board.entity_at(Position::new(1, 3)), None);
+        assert_eq!(game.board.entity_at(Position::new(4, 3)), None);
+    }
+
+    #[test]
+    fn moves_stop_at_units_hidden_by_the_fog() {
+        let mut game = Game::new(Board::new(Dimension::new(8, 3)));
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK,     Team::Red,  Position::new(1, 1))).unwrap();
+        let hidden   = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Blue, Position::new(4, 1))).unwrap();
+        game.units[tank].sight   = 1;
+        game.units[tank].actions = 4;
+        game.update_visibility();
+
+        assert!(!game.can_see(Team::Red, Position::new(4, 1)));
+        assert!(game.movement_range(tank).unwrap().contains(Position::new(5, 1)));
+        assert_eq!(game.route(tank, &[], Position::new(5, 1)).map(|path| path.len()), Some(4));
+
+        move_unit(&mut game, IntentToMove { entity: tank, to: Position::new(5, 1), path: None }).unwrap();
+
+        assert_eq!(game.units[tank].position, Position::new(3, 1));
+        assert_eq!(game.units[tank].actions, 0);
+        assert_eq!(game.units[hidden].position, Position::new(4, 1));
+        assert!(game.can_see(Team::Red, Position::new(4, 1)));
+    }
+}
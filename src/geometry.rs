@@ -0,0 +1,384 @@
+use std::str::{FromStr};
+
+use crate::{Dimension, Position};
+
+/// A way of measuring how far apart two positions are.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Metric {
+    /// Steps along the grid, so diagonals count twice.
+    Manhattan,
+
+    /// Steps in any of the eight directions, so diagonals count once.
+    Chebyshev,
+
+    /// The straight line distance.
+    Euclidean,
+
+    /// Steps across a hex grid in axial coordinates.
+    Hex
+}
+
+impl Metric {
+    /// Whether an offset from the origin lies within the radius.
+    pub fn reaches(&self, offset: Position, radius: u32) -> bool {
+        let dx = offset.x.unsigned_abs();
+        let dy = offset.y.unsigned_abs();
+
+        match self {
+            Metric::Manhattan => dx + dy <= radius,
+            Metric::Chebyshev => dx.max(dy) <= radius,
+            Metric::Euclidean => dx * dx + dy * dy <= radius * radius,
+            Metric::Hex       => Position::default().hex_distance(&offset) <= radius
+        }
+    }
+}
+
+/// How the tiles of a board connect to each other.
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum Topology {
+    /// Square tiles joined along their four edges.
+    #[default]
+    Square,
+
+    /// Hexagonal tiles in axial coordinates, joined to six neighbours. The
+    /// x axis runs east and the y axis runs south-east, so a rectangular
+    /// grid of tiles is drawn as a rhombus.
+    Hex
+}
+
+const SQUARE_OFFSETS: [Position; 4] = [
+    Position { x:  0, y: -1 },
+    Position { x:  1, y:  0 },
+    Position { x:  0, y:  1 },
+    Position { x: -1, y:  0 }
+];
+
+const HEX_OFFSETS: [Position; 6] = [
+    Position { x:  1, y: -1 },
+    Position { x:  1, y:  0 },
+    Position { x:  0, y:  1 },
+    Position { x: -1, y:  1 },
+    Position { x: -1, y:  0 },
+    Position { x:  0, y: -1 }
+];
+
+impl FromStr for Topology {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Square" => Ok(Topology::Square),
+            "Hex"    => Ok(Topology::Hex),
+            _        => Err(())
+        }
+    }
+}
+
+impl Topology {
+    /// The positions that are one step away.
+    pub fn neighbours(&self, position: Position) -> impl Iterator<Item = Position> {
+        let offsets: &'static [Position] = match self {
+            Topology::Square => &SQUARE_OFFSETS,
+            Topology::Hex    => &HEX_OFFSETS
+        };
+
+        offsets.iter().map(move |offset| position + *offset)
+    }
+
+    /// The number of steps between two positions.
+    pub fn distance(&self, from: Position, to: Position) -> u32 {
+        match self {
+            Topology::Square => from.manhatten_distance(&to),
+            Topology::Hex    => from.hex_distance(&to)
+        }
+    }
+
+    /// The metric that measures steps on the grid.
+    pub fn metric(&self) -> Metric {
+        match self {
+            Topology::Square => Metric::Manhattan,
+            Topology::Hex    => Metric::Hex
+        }
+    }
+
+    /// The console cell a position is drawn in. Hexes take two cells each
+    /// and every row is shifted half a hex further east than the last.
+    pub fn to_screen(&self, position: Position) -> Position {
+        match self {
+            Topology::Square => position,
+            Topology::Hex    => Position::new(2 * position.x + position.y, position.y)
+        }
+    }
+
+    /// The position drawn in a console cell. The empty cells between
+    /// hexes belong to the hex on their left.
+    pub fn from_screen(&self, cell: Position) -> Position {
+        match self {
+            Topology::Square => cell,
+            Topology::Hex    => Position::new((cell.x - cell.y).div_euclid(2), cell.y)
+        }
+    }
+
+    /// The console cells needed to draw a board of the size.
+    pub fn screen_size(&self, size: Dimension) -> Dimension {
+        match self {
+            Topology::Square => size,
+            Topology::Hex    => Dimension::new((2 * size.width + size.height).saturating_sub(1), size.height)
+        }
+    }
+}
+
+/// One of the four directions along the grid.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West
+}
+
+impl Direction {
+    /// The offset of one step in the direction.
+    pub fn offset(&self) -> Position {
+        match self {
+            Direction::North => Position::new( 0, -1),
+            Direction::East  => Position::new( 1,  0),
+            Direction::South => Position::new( 0,  1),
+            Direction::West  => Position::new(-1,  0)
+        }
+    }
+}
+
+/// The positions on a straight line between two positions, including both
+/// ends, following Bresenham's algorithm.
+#[derive(Debug, Clone)]
+pub struct Line {
+    current: Option<Position>,
+    end:     Position,
+    dx:      i32,
+    dy:      i32,
+    sx:      i32,
+    sy:      i32,
+    error:   i32
+}
+
+impl Line {
+    pub fn new(start: Position, end: Position) -> Self {
+        let dx =  (end.x - start.x).abs();
+        let dy = -(end.y - start.y).abs();
+
+        Line {
+            current: Some(start),
+            end,
+            dx,
+            dy,
+            sx:      if start.x < end.x { 1 } else { -1 },
+            sy:      if start.y < end.y { 1 } else { -1 },
+            error:   dx + dy
+        }
+    }
+}
+
+impl Iterator for Line {
+    type Item = Position;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.current?;
+        if position == self.end {
+            self.current = None;
+            return Some(position);
+        }
+
+        let mut next = position;
+        let doubled  = 2 * self.error;
+        if doubled >= self.dy {
+            self.error += self.dy;
+            next.x     += self.sx;
+        }
+
+        if doubled <= self.dx {
+            self.error += self.dx;
+            next.y     += self.sy;
+        }
+
+        self.current = Some(next);
+        Some(position)
+    }
+}
+
+impl Position {
+    /// The positions on a straight line to the other position, including
+    /// both ends.
+    pub fn line_to(&self, other: &Self) -> Line {
+        Line::new(*self, *other)
+    }
+
+    /// The positions on a straight line across a hex grid to the other
+    /// position, including both ends.
+    pub fn hex_line_to(&self, other: &Self) -> impl Iterator<Item = Position> {
+        let (from, to) = (*self, *other);
+        let steps      = from.hex_distance(&to);
+
+        (0..=steps).map(move |step| {
+            if steps == 0 {
+                return from;
+            }
+
+            // Nudged off the edges between hexes so that lines running
+            // along them always round the same way.
+            let t = step as f32 / steps as f32;
+            let x = from.x as f32 + (to.x - from.x) as f32 * t + 1e-4;
+            let y = from.y as f32 + (to.y - from.y) as f32 * t + 1e-4;
+
+            hex_round(x, y)
+        })
+    }
+
+    /// The positions of a rectangle with this position as its top left
+    /// corner, row by row.
+    pub fn rectangle(&self, width: u32, height: u32) -> impl Iterator<Item = Position> {
+        let origin = *self;
+
+        (0..height as i32).flat_map(move |y| {
+            (0..width as i32).map(move |x| origin + Position::new(x, y))
+        })
+    }
+
+    /// The positions within the radius, including this one. A Manhattan
+    /// area is a diamond, a Chebyshev area a square and a Euclidean area a
+    /// circle.
+    pub fn area(&self, radius: u32, metric: Metric) -> impl Iterator<Item = Position> {
+        let origin = *self;
+        let corner = origin - Position::new(radius as i32, radius as i32);
+        let side   = 2 * radius + 1;
+
+        corner
+            .rectangle(side, side)
+            .filter(move |position| metric.reaches(*position - origin, radius))
+    }
+
+    /// The positions exactly at the radius, that is within it but not
+    /// within the radius one smaller.
+    pub fn ring(&self, radius: u32, metric: Metric) -> impl Iterator<Item = Position> {
+        let origin = *self;
+
+        self.area(radius, metric)
+            .filter(move |position| radius == 0 || !metric.reaches(*position - origin, radius - 1))
+    }
+
+    /// The positions in front of this one in the direction, widening by
+    /// one tile to either side with every step, up to the length.
+    pub fn cone(&self, direction: Direction, length: u32) -> impl Iterator<Item = Position> {
+        let origin  = *self;
+        let forward = direction.offset();
+        let side    = Position::new(-forward.y, forward.x);
+
+        (1..=length as i32).flat_map(move |step| {
+            (-step..=step).map(move |across| origin + forward * step + side * across)
+        })
+    }
+}
+
+/// The hex containing a point given in fractional axial coordinates.
+fn hex_round(x: f32, y: f32) -> Position {
+    let z = -x - y;
+
+    let (mut rx, mut ry, rz) = (x.round(), y.round(), z.round());
+    let (dx, dy, dz)         = ((rx - x).abs(), (ry - y).abs(), (rz - z).abs());
+
+    if dx > dy && dx > dz {
+        rx = -ry - rz;
+    } else if dy > dz {
+        ry = -rx - rz;
+    }
+
+    Position::new(rx as i32, ry as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_includes_both_ends() {
+        let line: Vec<Position> = Position::new(0, 0).line_to(&Position::new(3, 1)).collect();
+
+        assert_eq!(line.first(), Some(&Position::new(0, 0)));
+        assert_eq!(line.last(),  Some(&Position::new(3, 1)));
+        assert_eq!(line.len(), 4);
+    }
+
+    #[test]
+    fn line_to_itself_is_one_position() {
+        let line: Vec<Position> = Position::new(2, 2).line_to(&Position::new(2, 2)).collect();
+
+        assert_eq!(line, vec![Position::new(2, 2)]);
+    }
+
+    #[test]
+    fn area_shapes_follow_the_metric() {
+        let origin = Position::new(0, 0);
+
+        assert_eq!(origin.area(1, Metric::Manhattan).count(), 5);
+        assert_eq!(origin.area(1, Metric::Chebyshev).count(), 9);
+        assert_eq!(origin.area(2, Metric::Euclidean).count(), 13);
+        assert_eq!(origin.area(1, Metric::Hex).count(), 7);
+    }
+
+    #[test]
+    fn ring_excludes_the_inside() {
+        let origin = Position::new(0, 0);
+        let ring: Vec<Position> = origin.ring(2, Metric::Manhattan).collect();
+
+        assert_eq!(ring.len(), 8);
+        assert!(ring.iter().all(|position| origin.manhatten_distance(position) == 2));
+        assert_eq!(origin.ring(0, Metric::Manhattan).collect::<Vec<_>>(), vec![origin]);
+    }
+
+    #[test]
+    fn cone_widens_with_each_step() {
+        let cone: Vec<Position> = Position::new(0, 0).cone(Direction::East, 2).collect();
+
+        assert_eq!(cone.len(), 3 + 5);
+        assert!(cone.iter().all(|position| position.x > 0));
+        assert!(cone.contains(&Position::new(2, -2)));
+    }
+
+    #[test]
+    fn rectangle_runs_row_by_row() {
+        let rectangle: Vec<Position> = Position::new(1, 1).rectangle(2, 2).collect();
+
+        assert_eq!(rectangle, vec![
+            Position::new(1, 1),
+            Position::new(2, 1),
+            Position::new(1, 2),
+            Position::new(2, 2)
+        ]);
+    }
+
+    #[test]
+    fn hex_neighbours_are_one_step_away() {
+        let origin = Position::new(3, 3);
+
+        assert_eq!(Topology::Hex.neighbours(origin).count(), 6);
+        assert!(Topology::Hex.neighbours(origin).all(|neighbour| Topology::Hex.distance(origin, neighbour) == 1));
+    }
+
+    #[test]
+    fn hex_screen_cells_map_back_to_their_hex() {
+        for position in Position::new(0, 0).rectangle(4, 4) {
+            let cell = Topology::Hex.to_screen(position);
+
+            assert_eq!(Topology::Hex.from_screen(cell), position);
+            assert_eq!(Topology::Hex.from_screen(cell + Position::new(1, 0)), position);
+        }
+    }
+
+    #[test]
+    fn hex_line_steps_between_neighbours() {
+        let line: Vec<Position> = Position::new(0, 0).hex_line_to(&Position::new(3, -1)).collect();
+
+        assert_eq!(line.len(), 4);
+        assert!(line.windows(2).all(|pair| pair[0].hex_distance(&pair[1]) == 1));
+    }
+}
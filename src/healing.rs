@@ -0,0 +1,166 @@
+use crate::{EntityIndex, Game, Team, Unit, UnitClass, Special, HealingAtPos};
+
+/// The health restored by one repair or healing action.
+pub const HEALING_PER_ACTION: u32 = 1;
+
+/// The health restored to infantry inside a friendly Barracks when their
+/// team ends its turn.
+pub const SHELTER_HEALING: u32 = 1;
+
+/// Whether the unit is able to restore the patient's health at all.
+/// Engineers repair vehicles and structures while medics heal infantry.
+pub fn can_heal(healer: &Unit, patient: &Unit) -> bool {
+    if healer.team != patient.team {
+        return false;
+    }
+
+    if patient.class == UnitClass::Infantry {
+        healer.has_special(Special::Heal)
+    } else {
+        healer.has_special(Special::Repair)
+    }
+}
+
+/// The health the unit will have once the queued healing resolves.
+pub fn healed_health(game: &Game, unit: &Unit) -> u32 {
+    let queued: u32 = game.healing_queue
+        .iter()
+        .filter(|healing| healing.at == unit.position)
+        .map(|healing| healing.amount)
+        .sum();
+
+    (unit.health + queued).min(unit.health_max)
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToHeal {
+    pub entity:         EntityIndex,
+    pub patient_entity: EntityIndex
+}
+
+#[derive(Debug)]
+pub enum HealError {
+    UnitInvalid,
+    UnitExhausted,
+    UnitCannotHeal,
+    PatientInvalid,
+    PatientOutOfRange,
+    PatientUnharmed
+}
+
+/// Checks that the unit can heal the patient.
+pub fn validate_heal(game: &Game, intent: &IntentToHeal) -> Result<(), HealError> {
+    if intent.entity == intent.patient_entity {
+        return Err(HealError::PatientInvalid);
+    }
+
+    let unit    = game.units.get(intent.entity).ok_or(HealError::UnitInvalid)?;
+    let patient = game.units.get(intent.patient_entity).ok_or(HealError::PatientInvalid)?;
+
+    if unit.actions == 0 {
+        return Err(HealError::UnitExhausted);
+    }
+
+    if !can_heal(unit, patient) {
+        return Err(HealError::UnitCannotHeal);
+    }
+
+    if game.board.distance(unit.position, patient.position) != 1 {
+        return Err(HealError::PatientOutOfRange);
+    }
+
+    if healed_health(game, patient) >= patient.health_max {
+        return Err(HealError::PatientUnharmed);
+    }
+
+    Ok(())
+}
+
+/// Spends one of the unit's actions restoring the health of an adjacent
+/// friendly unit. The healing is queued and resolves alongside the damage
+/// queue in `bring_out_your_dead`.
+pub fn heal_unit(game: &mut Game, intent: IntentToHeal) -> Result<(), HealError> {
+    validate_heal(game, &intent)?;
+
+    let at = game.units[intent.patient_entity].position;
+    game.healing_queue.push(HealingAtPos::new(at, HEALING_PER_ACTION));
+    game.units[intent.entity].actions -= 1;
+
+    Ok(())
+}
+
+/// Queues healing for the team's wounded infantry sheltering inside one of
+/// its Barracks.
+pub fn shelter_infantry(game: &mut Game, team: Team) {
+    let wounded: Vec<HealingAtPos> = game.units
+        .iter()
+        .filter(|(_, unit)| unit.team == team && unit.has_special(Special::Shelter))
+        .flat_map(|(_, shelter)| {
+            shelter.cargo
+                .iter()
+                .enumerate()
+                .filter(|(_, unit)| unit.class == UnitClass::Infantry && unit.health < unit.health_max)
+                .map(move |(cargo, _)| HealingAtPos::aboard(shelter.position, cargo, SHELTER_HEALING))
+        })
+        .collect();
+
+    game.healing_queue.extend(wounded);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Position, UnitKind, SpawnData, IntentToEmbark, EmbarkError, embark_unit, bring_out_your_dead};
+
+    #[test]
+    fn medics_heal_infantry_and_engineers_repair_vehicles() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let engineer = game.spawn(SpawnData::new(UnitKind::ENGINEER, Team::Red, Position::new(1, 1))).unwrap();
+        let medic    = game.spawn(SpawnData::new(UnitKind::MEDIC,    Team::Red, Position::new(1, 3))).unwrap();
+        let infantry = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Red, Position::new(1, 2))).unwrap();
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK,     Team::Red, Position::new(2, 1))).unwrap();
+        game.units[infantry].health = 1;
+        game.units[tank].health     = 2;
+
+        let wrong = heal_unit(&mut game, IntentToHeal { entity: engineer, patient_entity: infantry });
+        assert!(matches!(wrong, Err(HealError::UnitCannotHeal)));
+
+        heal_unit(&mut game, IntentToHeal { entity: medic,    patient_entity: infantry }).unwrap();
+        heal_unit(&mut game, IntentToHeal { entity: engineer, patient_entity: tank }).unwrap();
+
+        let full = heal_unit(&mut game, IntentToHeal { entity: medic, patient_entity: infantry });
+        assert!(matches!(full, Err(HealError::PatientUnharmed)));
+
+        assert_eq!(game.units[infantry].health, 1);
+        bring_out_your_dead(&mut game);
+        assert_eq!(game.units[infantry].health, 2);
+        assert_eq!(game.units[tank].health, 3);
+        assert_eq!(game.units[medic].actions, 1);
+    }
+
+    #[test]
+    fn infantry_recover_inside_a_barracks() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        game.player  = Team::Red;
+        let barracks = game.spawn(SpawnData::new(UnitKind::BARRACKS, Team::Red, Position::new(1, 1))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::TANK, Team::Blue, Position::new(4, 4))).unwrap();
+
+        let sheltered = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Red, Position::new(1, 2))).unwrap();
+        let exposed   = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Red, Position::new(3, 3))).unwrap();
+        game.units[sheltered].health = 1;
+        game.units[exposed].health   = 1;
+
+        let tank = game.spawn(SpawnData::new(UnitKind::TANK, Team::Red, Position::new(0, 1))).unwrap();
+        let refused = embark_unit(&mut game, IntentToEmbark { entity: tank, transport: barracks });
+        assert!(matches!(refused, Err(EmbarkError::UnitCannotBoard)));
+
+        embark_unit(&mut game, IntentToEmbark { entity: sheltered, transport: barracks }).unwrap();
+
+        assert!(game.next_turn());
+        assert_eq!(game.units[barracks].cargo[0].health, 1);
+        bring_out_your_dead(&mut game);
+
+        assert_eq!(game.units[barracks].cargo[0].health, 2);
+        assert_eq!(game.units[exposed].health, 1);
+    }
+}
@@ -0,0 +1,391 @@
+use crate::{EntityIndex, Game, Position, Command, Visibility};
+use crate::game::*;
+use crate::transport::*;
+use crate::healing::*;
+use crate::ability::*;
+
+/// What an intent changed, kept so that it can be reverted.
+#[derive(Debug, Clone)]
+enum Change {
+    Moved       { from: Position, to: Position },
+    Attacked    { health: u32, queued: usize },
+    Spawned     { spawned: EntityIndex, funds: u32 },
+    Embarked    { transport: EntityIndex, from: Position },
+    Disembarked { disembarked: EntityIndex, cargo: usize },
+    Healed      { queued: usize }
+}
+
+#[derive(Debug, Clone)]
+struct Record {
+    intent:  Intent,
+    entity:  EntityIndex,
+    actions: u32,
+    seen:    Option<Visibility>,
+    change:  Change
+}
+
+/// The intents applied during the current turn. Everything can be undone
+/// until the damage queue is resolved or an intent reveals a unit hidden
+/// by the fog of war, after which earlier intents are final. The history
+/// is cleared at the start of every turn.
+#[derive(Debug, Default)]
+pub struct History {
+    done:   Vec<Record>,
+    undone: Vec<Intent>
+}
+
+impl History {
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    /// Makes every intent applied so far permanent.
+    pub fn seal(&mut self) {
+        self.done.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+}
+
+/// Applies the intent and records it in the history, discarding anything
+/// that could have been redone.
+pub fn apply_intent(game: &mut Game, intent: Intent) -> Result<(), IntentError> {
+    let command = Command::from_intent(game, &intent);
+    let result  = execute(game, intent);
+
+    // Ending the turn changes the game even when the match is over.
+    if let Some(command) = command {
+        if result.is_ok() || matches!(command, Command::EndTurn) {
+            game.record(command);
+        }
+    }
+
+    result?;
+    game.history.undone.clear();
+
+    Ok(())
+}
+
+/// Reverts the most recent intent, returning false if there was nothing
+/// left to undo.
+pub fn undo(game: &mut Game) -> bool {
+    let record = match game.history.done.pop() {
+        Some(record) => record,
+        None         => return false
+    };
+
+    let mut intent = record.intent;
+
+    match record.change {
+        Change::Moved { from, to } => {
+            game.board.swap_between(to, from);
+            if let Some(unit) = game.units.get_mut(record.entity) {
+                unit.move_to(from);
+            }
+        },
+
+        Change::Healed { queued } => {
+            game.healing_queue.truncate(queued);
+        },
+
+        Change::Attacked { health, queued } => {
+            game.damage_queue.truncate(queued);
+            if let Some(unit) = game.units.get_mut(record.entity) {
+                unit.health = health;
+            }
+        },
+
+        Change::Spawned { spawned, funds } => {
+            if let Some(unit) = game.units.remove(spawned) {
+                game.board.remove_at(unit.position);
+                game.funds.insert(unit.team, funds);
+            }
+        },
+
+        Change::Embarked { transport, from } => {
+            if let Some(mut unit) = game.units.get_mut(transport).and_then(|transport| transport.cargo.pop()) {
+                unit.position = from;
+                unit.actions  = record.actions;
+
+                // The unit comes back under a new index, so point the
+                // intent at it in case it is redone.
+                let restored = game.units.insert(unit);
+                game.board.insert_at(from, restored);
+                intent = Intent::Embark(IntentToEmbark { entity: restored, transport });
+            }
+        },
+
+        Change::Disembarked { disembarked, cargo } => {
+            if let Some(unit) = game.units.remove(disembarked) {
+                game.board.remove_at(unit.position);
+                if let Some(transport) = game.units.get_mut(record.entity) {
+                    let cargo = cargo.min(transport.cargo.len());
+                    transport.cargo.insert(cargo, unit);
+                }
+            }
+        }
+    }
+
+    if let Some(unit) = game.units.get_mut(record.entity) {
+        unit.actions = record.actions;
+    }
+
+    game.update_visibility();
+
+    // Tiles explored by the intent are forgotten again.
+    if let Some(seen) = record.seen {
+        game.visibility.insert(game.player, seen);
+    }
+
+    game.history.undone.push(intent);
+    game.record(Command::Undo);
+
+    true
+}
+
+/// Applies the most recently undone intent again. Returns None if there
+/// was nothing to redo. Units created by an intent that was undone come
+/// back with new entity indices, so redoing later orders given to them
+/// fails with `UnitInvalid`.
+pub fn redo(game: &mut Game) -> Option<Result<(), IntentError>> {
+    let intent = game.history.undone.pop()?;
+    game.record(Command::Redo);
+
+    Some(execute(game, intent))
+}
+
+/// The state of the unit giving an order, taken before the order is
+/// carried out.
+struct Before {
+    actions:  u32,
+    position: Position,
+    health:   u32,
+    funds:    u32
+}
+
+fn execute(game: &mut Game, intent: Intent) -> Result<(), IntentError> {
+    let entity = intent.entity();
+    let before = entity
+        .and_then(|entity| game.units.get(entity))
+        .map(|unit| Before {
+            actions:  unit.actions,
+            position: unit.position,
+            health:   unit.health,
+            funds:    game.funds(unit.team)
+        });
+
+    let queued  = game.damage_queue.len();
+    let healing = game.healing_queue.len();
+    let record  = intent.clone();
+    let seen    = game.visibility.get(&game.player).cloned();
+
+    validate_owner(game, &intent)?;
+    validate_ability(game, &intent)?;
+
+    let change = match intent {
+        Intent::Move(intent) => {
+            let entity = intent.entity;
+            move_unit(game, intent).map_err(IntentError::Move)?;
+
+            // A unit that bumped into a hidden unit stops short.
+            let to = game.units[entity].position;
+            before.as_ref().map(|before| Change::Moved { from: before.position, to })
+        },
+
+        Intent::Attack(intent) => {
+            attack_with_unit(game, intent).map_err(IntentError::Attack)?;
+            before.as_ref().map(|before| Change::Attacked { health: before.health, queued })
+        },
+
+        Intent::Build(intent) => {
+            let spawned = build_with_unit(game, intent).map_err(IntentError::Build)?;
+            before.as_ref().map(|before| Change::Spawned { spawned, funds: before.funds })
+        },
+
+        Intent::Produce(intent) => {
+            let spawned = produce_with_unit(game, intent).map_err(IntentError::Produce)?;
+            before.as_ref().map(|before| Change::Spawned { spawned, funds: before.funds })
+        },
+
+        Intent::Embark(intent) => {
+            let transport = intent.transport;
+            embark_unit(game, intent).map_err(IntentError::Embark)?;
+            before.as_ref().map(|before| Change::Embarked { transport, from: before.position })
+        },
+
+        Intent::Disembark(intent) => {
+            let cargo       = intent.cargo;
+            let disembarked = disembark_unit(game, intent).map_err(IntentError::Disembark)?;
+            Some(Change::Disembarked { disembarked, cargo })
+        },
+
+        Intent::Heal(intent) => {
+            heal_unit(game, intent).map_err(IntentError::Heal)?;
+            Some(Change::Healed { queued: healing })
+        },
+
+        Intent::Detonate(intent) => {
+            detonate_unit(game, intent).map_err(IntentError::Detonate)?;
+            before.as_ref().map(|before| Change::Attacked { health: before.health, queued })
+        },
+
+        Intent::EndTurn => {
+            return if game.next_turn() {
+                Ok(())
+            } else {
+                Err(IntentError::GameOver)
+            };
+        }
+    };
+
+    // Taking back an intent that revealed enemy units would let the
+    // player keep what they learned, so it becomes final instead.
+    let enemies  = game.units
+        .iter()
+        .filter(|(_, unit)| unit.team != game.player)
+        .map(|(_, unit)| unit.position);
+
+    let blank    = Visibility::new(game.board.size());
+    let revealed = game.rules.fog_of_war && match game.visibility.get(&game.player) {
+        Some(now) => now.reveals_any(seen.as_ref().unwrap_or(&blank), enemies),
+        None      => false
+    };
+
+    if revealed {
+        game.history.seal();
+        return Ok(());
+    }
+
+    if let (Some(entity), Some(before), Some(change)) = (entity, before, change) {
+        game.history.done.push(Record {
+            intent:  record,
+            entity,
+            actions: before.actions,
+            seen,
+            change
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Team, UnitKind, SpawnData};
+
+    fn game_with_fog(fog_of_war: bool) -> (Game, EntityIndex, EntityIndex) {
+        let mut game = Game::new(Board::new(Dimension::new(12, 5)));
+        game.player = Team::Red;
+        game.rules.fog_of_war = fog_of_war;
+
+        let tank  = game.spawn(SpawnData::new(UnitKind::TANK, Team::Red, Position::new(2, 2))).unwrap();
+        let enemy = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Blue, Position::new(4, 2))).unwrap();
+
+        (game, tank, enemy)
+    }
+
+    fn move_to(entity: EntityIndex, x: i32, y: i32) -> Intent {
+        Intent::Move(IntentToMove {
+            entity,
+            to:   Position::new(x, y),
+            path: None
+        })
+    }
+
+    #[test]
+    fn undo_and_redo_a_move() {
+        let (mut game, tank, _) = game_with_fog(false);
+        let actions = game.units[tank].actions;
+
+        apply_intent(&mut game, move_to(tank, 2, 3)).unwrap();
+        assert!(undo(&mut game));
+        assert_eq!(game.units[tank].position, Position::new(2, 2));
+        assert_eq!(game.units[tank].actions, actions);
+        assert_eq!(game.board.entity_at(Position::new(2, 3)), None);
+
+        assert!(redo(&mut game).unwrap().is_ok());
+        assert_eq!(game.units[tank].position, Position::new(2, 3));
+        assert!(!game.history.can_redo());
+    }
+
+    #[test]
+    fn undoing_a_production_refunds_it() {
+        let (mut game, _, _) = game_with_fog(false);
+        let factory = game.spawn(SpawnData::new(UnitKind::FACTORY, Team::Red, Position::new(6, 2))).unwrap();
+        game.funds.insert(Team::Red, 300);
+
+        apply_intent(&mut game, Intent::Produce(IntentToProduce {
+            entity: factory,
+            kind:   UnitKind::TANK,
+            at:     Position::new(6, 3)
+        })).unwrap();
+
+        assert_eq!(game.funds(Team::Red), 0);
+        assert!(undo(&mut game));
+        assert_eq!(game.funds(Team::Red), 300);
+        assert_eq!(game.units[factory].actions, game.units[factory].actions_max);
+        assert_eq!(game.board.entity_at(Position::new(6, 3)), None);
+        assert_eq!(game.units.len(), 3);
+    }
+
+    #[test]
+    fn resolving_damage_seals_the_history() {
+        let (mut game, tank, enemy) = game_with_fog(false);
+
+        apply_intent(&mut game, Intent::Attack(IntentToAttack { entity: tank, target_entity: enemy })).unwrap();
+        assert!(game.history.can_undo());
+
+        bring_out_your_dead(&mut game);
+        assert!(!game.history.can_undo());
+        assert!(!undo(&mut game));
+    }
+
+    #[test]
+    fn moves_under_the_fog_forget_what_they_explored_when_undone() {
+        let (mut game, tank, _) = game_with_fog(true);
+        game.units[tank].sight = 1;
+        game.visibility.clear();
+        game.update_visibility();
+
+        apply_intent(&mut game, move_to(tank, 1, 2)).unwrap();
+        assert!(game.visibility[&Team::Red].is_explored(Position::new(0, 2)));
+        assert!(game.history.can_undo());
+
+        assert!(undo(&mut game));
+        assert_eq!(game.units[tank].position, Position::new(2, 2));
+        assert!(!game.visibility[&Team::Red].is_explored(Position::new(0, 2)));
+        assert!(game.visibility[&Team::Red].is_visible(Position::new(3, 2)));
+    }
+
+    #[test]
+    fn moves_that_reveal_enemies_cannot_be_undone() {
+        let (mut game, tank, _) = game_with_fog(true);
+        game.units[tank].sight = 1;
+        game.update_visibility();
+
+        assert!(!game.can_see(Team::Red, Position::new(4, 2)));
+        apply_intent(&mut game, move_to(tank, 3, 2)).unwrap();
+
+        assert!(game.can_see(Team::Red, Position::new(4, 2)));
+        assert!(!game.history.can_undo());
+        assert!(!undo(&mut game));
+    }
+
+    #[test]
+    fn orders_for_the_other_team_are_rejected() {
+        let (mut game, _, enemy) = game_with_fog(false);
+
+        let result = apply_intent(&mut game, move_to(enemy, 5, 2));
+
+        assert!(matches!(result, Err(IntentError::NotYourUnit)));
+        assert_eq!(game.units[enemy].position, Position::new(4, 2));
+    }
+}
@@ -11,7 +11,7 @@ use tcod::input::Key     as TcodKey;
 use tcod::input::KeyCode as TcodKeyCode;
 use tcod::input::Mouse   as TcodMouse;
 
-use crate::{Position, Dimension};
+use crate::{Position, Dimension, Topology};
 
 pub struct Input {
     keys_last_frame: [bool; KeyCode::count()],
@@ -36,7 +36,7 @@ impl Input {
         }
     }
 
-    pub fn update(&mut self, world_size: Dimension, world_offset: Position) {
+    pub fn update(&mut self, world_size: Dimension, topology: Topology, world_offset: Position) {
         for i in 0..KeyCode::count() {
             self.keys_last_frame[i] = self.keys_this_frame[i];
         }
@@ -74,7 +74,7 @@ impl Input {
                 Some((_, TcodEvent::Mouse(tcod_mouse))) => {
                     self.mouse.pixel_pos = Position::new(tcod_mouse.x  as i32, tcod_mouse.y  as i32);
                     self.mouse.cell_pos  = Position::new(tcod_mouse.cx as i32, tcod_mouse.cy as i32);
-                    self.mouse.world_pos = self.mouse.cell_pos.into_world_pos(world_size, world_offset);
+                    self.mouse.world_pos = self.mouse.cell_pos.into_world_pos(world_size, world_offset, topology);
                 },
                 
                 None => {
@@ -135,7 +135,7 @@ pub enum KeyCode {
     A, B, C, D, E, F, G, H, I, J, K, L, M,
     N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
 
-    Right, Up, Left, Down, Space, Escape, Delete,
+    Right, Up, Left, Down, Space, Escape, Delete, Shift,
 
     // Ensure this is the last item in the list.
     // It is used for determining the number of
@@ -155,9 +155,10 @@ impl Default for KeyCode {
     }
 }
 
-impl From<TcodKey> for KeyCode {
-    fn from(tcod_key: TcodKey) -> Self {
-        match tcod_key.printable.to_ascii_uppercase() {
+impl KeyCode {
+    /// The key that types the character, ignoring case.
+    pub fn from_char(character: char) -> Self {
+        match character.to_ascii_uppercase() {
             '1' => KeyCode::A1, '2' => KeyCode::A2, '3' => KeyCode::A3,
             '4' => KeyCode::A4, '5' => KeyCode::A5, '6' => KeyCode::A6,
             '7' => KeyCode::A7, '8' => KeyCode::A8, '9' => KeyCode::A9,
@@ -172,8 +173,16 @@ impl From<TcodKey> for KeyCode {
             'S' => KeyCode::S, 'T' => KeyCode::T, 'U' => KeyCode::U,
             'V' => KeyCode::V, 'W' => KeyCode::W, 'X' => KeyCode::X,
             'Y' => KeyCode::Y, 'Z' => KeyCode::Z,
-            
-            _   => match tcod_key.code {
+
+            _   => KeyCode::Unknown
+        }
+    }
+}
+
+impl From<TcodKey> for KeyCode {
+    fn from(tcod_key: TcodKey) -> Self {
+        match KeyCode::from_char(tcod_key.printable) {
+            KeyCode::Unknown => match tcod_key.code {
                 TcodKeyCode::Right => KeyCode::Right,
                 TcodKeyCode::Up    => KeyCode::Up,
                 TcodKeyCode::Left  => KeyCode::Left,
@@ -182,11 +191,14 @@ impl From<TcodKey> for KeyCode {
                 TcodKeyCode::Spacebar => KeyCode::Space,
                 TcodKeyCode::Escape   => KeyCode::Escape,
                 TcodKeyCode::Delete   => KeyCode::Delete,
+                TcodKeyCode::Shift    => KeyCode::Shift,
                 
                 _ => {
                     KeyCode::Unknown
                 }
-            }
+            },
+
+            code => code
         }
     }
 }
@@ -0,0 +1,45 @@
+extern crate generational_arena;
+
+pub use generational_arena::Index as EntityIndex;
+
+pub mod entity;
+pub mod definitions;
+pub mod position;
+pub mod geometry;
+pub mod board;
+pub mod navigation;
+pub mod action_circle;
+pub mod game;
+pub mod combat;
+pub mod explosion;
+pub mod transport;
+pub mod healing;
+pub mod ability;
+pub mod victory;
+pub mod save;
+pub mod map;
+pub mod ai;
+pub mod history;
+pub mod visibility;
+pub mod replay;
+
+pub use entity::*;
+pub use definitions::*;
+pub use position::*;
+pub use geometry::*;
+pub use board::*;
+pub use navigation::*;
+pub use action_circle::*;
+pub use game::*;
+pub use combat::*;
+pub use explosion::*;
+pub use transport::*;
+pub use healing::*;
+pub use ability::*;
+pub use victory::*;
+pub use save::*;
+pub use map::*;
+pub use ai::*;
+pub use history::*;
+pub use visibility::*;
+pub use replay::*;
@@ -1,257 +1,59 @@
 extern crate tcod;
-extern crate generational_arena;
+extern crate emulation;
 
 use tcod::console::*;
 use tcod::colors::*;
 
-use generational_arena::Arena;
-use generational_arena::Index as EntityIndex;
+use std::collections::{HashMap};
+use std::path::{Path};
+use std::thread;
+use std::time::{Duration};
+
+use emulation::*;
 
-mod entity;
-mod position;
-mod board;
-mod action_circle;
 mod input;
 mod utilities;
 mod menu;
+mod palette;
 
-use entity::*;
-use position::*;
-use board::*;
-use action_circle::*;
 use input::*;
 use utilities::*;
 use menu::*;
+use palette::*;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 enum PlayerState {
     Selecting,
     Controlling(EntityIndex),
-    Moving(EntityIndex),
-    Attacking(EntityIndex),
-    Building(EntityIndex),
+    Using(EntityIndex, AbilityKind),
+    Replaying { step: usize, length: usize },
     GameOver
 }
 
-#[derive(Debug)]
-struct Game {
-    player_state: PlayerState,
-    player:       Team,
-    board:        Board,
-
-    units:        Arena<Unit>,
-    damage_queue: Vec<DamageAtPos>,
-}
-
-#[derive(Debug)]
-pub struct SpawnData {
-    kind:     UnitKind,
-    team:     Team,
-    position: Position
-}
-
-impl SpawnData {
-    fn new(kind: UnitKind, team: Team, position: Position) -> Self {
-        SpawnData {
-            kind,
-            team,
-            position
-        }
-    }
-}
-
-#[derive(Debug)]
-pub enum SpawnError {
-    PositionOutOfBounds,
-    PositionOccupied
-}
-
-impl Game {
-    fn spawn(&mut self, data: SpawnData) -> Result<EntityIndex, SpawnError> {
-        self.board.to_index(data.position).ok_or(SpawnError::PositionOutOfBounds)?;
-        
-        if self.board.entity_at(data.position).is_some() {
-            return Err(SpawnError::PositionOccupied);
-        }
-
-        let unit = Unit::new(data.kind, data.team, data.position);
-        let entity = self.units.insert(unit);
-
-        self.board.insert_at(data.position, entity);
-
-        Ok(entity)
-    }
-
-    fn next_turn(&mut self) -> bool {
-        let current_team  = self.player;
-        let mut next_team = get_next_team(current_team);
-
-        let mut next_turn_valid = false;
-
-        while !next_turn_valid && current_team != next_team {
-            println!("Checking {:?}...", next_team);
-
-            for (_, unit) in &self.units {
-                if unit.team == next_team {
-                    next_turn_valid = true;
-                    break;
-                }
-            }
-
-            if !next_turn_valid {
-                next_team = get_next_team(next_team);
-            }
-        }
-
-        if next_turn_valid {
-            self.player       = next_team;
-            self.player_state = PlayerState::Selecting;
-            
-            for (_, unit) in &mut self.units {
-                if unit.team == self.player {
-                    unit.actions = unit.actions_max;
-                }
-            }
+const QUICKSAVE_PATH:   &str = "quicksave.sav";
+const DEFAULT_MAP_PATH: &str = "res/Maps/Skirmish.map";
+const DEFINITIONS_PATH: &str = "res/Units.def";
+const REPLAY_PATH:      &str = "replay.rep";
 
-            println!("{:?}'s turn!", self.player);
-
-            true
-        } else {
-            self.player_state = PlayerState::GameOver;
-            
-            println!("Game over!");
-
-            false
-        }
-    }
-}
+const AI_STEP_DELAY: Duration = Duration::from_millis(250);
 
 pub struct Graphics {
     pub root:         Root,
     pub board:        Offscreen,
-    pub board_offset: Position
-}
 
-#[derive(Debug)]
-pub struct IntentToMove {
-    pub entity: EntityIndex,
-    pub to:     Position
-}
-
-#[derive(Debug)]
-enum MoveError {
-    UnitInvalid,
-    UnitExhausted,
-    TerrainIncompatible,
-    DestinationOccupied,
-    DestinationUnreachable
-}
-
-fn move_unit(game: &mut Game, intent: IntentToMove) -> Result<(), MoveError> {
-    let mut unit = game.units.get_mut(intent.entity).ok_or(MoveError::UnitInvalid)?;
-
-    if unit.actions == 0 {
-        return Err(MoveError::UnitExhausted);
-    }
-
-    if game.board.entity_at(intent.to).is_some() {
-        return Err(MoveError::DestinationOccupied);
-    }
-
-    let action_circle = ActionCircle::new(unit.position, unit.actions, Some(unit.space), &game.board);
-    if !action_circle.contains(intent.to) {
-        return Err(MoveError::DestinationUnreachable);
-    }
-
-    let tile = game.board.tile_at(intent.to).unwrap();
-    if !unit.space.can_traverse(tile.traverse()) {
-        return Err(MoveError::TerrainIncompatible);
-    }
-    
-    game.board.swap_between(unit.position, intent.to);
-
-    unit.position = intent.to;
-
-    unit.actions -= action_circle.cost_to(intent.to).unwrap();
-
-    Ok(())
-}
-
-#[derive(Debug)]
-struct IntentToAttack {
-    entity:        EntityIndex,
-    target_entity: EntityIndex
-}
-
-#[derive(Debug)]
-enum AttackError {
-    UnitInvalid,
-    UnitExhausted,
-    TargetInvalid,
-    TargetFriendly,
-    TargetOutOfRange
-}
-
-#[derive(Debug)]
-struct DamageAtPos {
-    at:     Position,
-    amount: u32
-}
-
-impl DamageAtPos {
-    fn new(at: Position, amount: u32) -> Self {
-        DamageAtPos {
-            at,
-            amount
-        }
-    }
-}
-
-fn attack_with_unit(game: &mut Game, intent: IntentToAttack) -> Result<(), AttackError> {
-    if intent.entity == intent.target_entity {
-        return Err(AttackError::TargetFriendly);
-    }
-
-    let (unit, target) = game.units.get2_mut(intent.entity, intent.target_entity);
-    
-    let mut unit = unit.ok_or(AttackError::UnitInvalid)?;
-    let target   = target.ok_or(AttackError::TargetInvalid)?;
-
-    let position        = unit.position;
-    let target_position = target.position;
-    let action_circle   = ActionCircle::new(position, unit.range, Some(unit.space), &game.board);
-    
-    if !action_circle.contains(target_position) {
-        return Err(AttackError::TargetOutOfRange);
-    }
-    
-    if unit.team == target.team {
-        return Err(AttackError::TargetFriendly);
-    }
-
-    if unit.actions == 0 {
-        return Err(AttackError::UnitExhausted);
-    }
-
-    let damage = DamageAtPos::new(target_position, unit.damage);
-    game.damage_queue.push(damage);
-
-    if unit.kind == UnitKind::Missile {
-        unit.health = 0;
-        let explosion_radius = target_position.radius(2);
-        for position in explosion_radius {
-            let damage = DamageAtPos::new(position, unit.damage);
-            game.damage_queue.push(damage);
-        }
-    }
-
-    unit.actions = 0;
-    
-    Ok(())
+    /// The board laid out as its topology is drawn, one tile per cell on
+    /// square boards and spread out on hex boards.
+    pub layout:       Offscreen,
+    pub board_offset: Position
 }
 
-fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
+/// Draws the game as the viewing team sees it, or everything if there is
+/// no viewer.
+fn draw(game: &Game, viewer: Option<Team>, state: PlayerState, waypoints: &[Position], graphics: &mut Graphics, input: &Input) {
     let board = &game.board;
+
+    let can_see      = |position: Position| viewer.is_none_or(|team| game.can_see(team, position));
+    let has_explored = |position: Position| viewer.is_none_or(|team| game.has_explored(team, position));
     
     graphics.root.clear();
 
@@ -260,9 +62,19 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
         for x in 0..board.width() {
             let position   = Position::new(x as i32, y as i32);
             let tile       = board.tile_at(position).unwrap();
-            let fore_color = tile.fore_color();
-            let back_color = tile.back_color();
-            let glyph      = tile.glyph();
+            let mut fore_color = tile.fore_color();
+            let mut back_color = tile.back_color();
+            let mut glyph      = tile.glyph();
+
+            // Unexplored tiles are blank and remembered ones are shaded.
+            if !has_explored(position) {
+                fore_color = BLACK;
+                back_color = BLACK;
+                glyph      = ' ';
+            } else if !can_see(position) {
+                fore_color = darken(fore_color);
+                back_color = darken(back_color);
+            }
 
             graphics.board.put_char_ex(
                 x as i32,
@@ -276,6 +88,10 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
 
     // Draw entities
     for (_, unit) in &game.units {
+        if !can_see(unit.position) {
+            continue;
+        }
+
         graphics.board.set_char(
             unit.position.x,
             unit.position.y,
@@ -297,19 +113,39 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
         }
     }
 
+    // Mark the tiles that will be hit once the pending attacks resolve.
+    for damage in &game.damage_queue {
+        graphics.board.set_char_background(
+            damage.at.x,
+            damage.at.y,
+            DARK_RED,
+            BackgroundFlag::Set
+        );
+    }
+
+    // Mark the tiles that will be healed once the queue resolves.
+    for healing in &game.healing_queue {
+        graphics.board.set_char_background(
+            healing.at.x,
+            healing.at.y,
+            DARK_CHARTREUSE,
+            BackgroundFlag::Set
+        );
+    }
+
     // Highlight the selected entity
-    if let PlayerState::Controlling(entity) = game.player_state {
+    if let PlayerState::Controlling(entity) = state {
         let unit = game.units.get(entity).unwrap();
     
         invert_cell(&mut graphics.board, unit.position);
     }
 
-    match game.player_state {
-        PlayerState::Moving(entity) => {
-            let unit = game.units.get(entity).unwrap();
-            invert_cell(&mut graphics.board, unit.position);
+    if let PlayerState::Using(entity, ability) = state {
+        let unit = game.units.get(entity).unwrap();
+        invert_cell(&mut graphics.board, unit.position);
 
-            let action_circle = ActionCircle::new(unit.position, unit.actions, Some(unit.space), &game.board);
+        if ability == AbilityKind::Move {
+            let action_circle = game.movement_range(entity).unwrap();
             for (position, _) in action_circle {
                 graphics.board.set_char_background(
                     position.x,
@@ -318,44 +154,57 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
                     BackgroundFlag::Add
                 );
             }
-        },
-
-        PlayerState::Attacking(entity) => {
-            let unit = game.units.get(entity).unwrap();
-            invert_cell(&mut graphics.board, unit.position);
 
-            if unit.actions != 0 {
-                let action_circle = ActionCircle::new(unit.position, unit.range, Some(unit.space), &game.board);
-                for (position, _) in action_circle {    
+            // Preview the route to the hovered tile.
+            let world_pos = input.mouse().world_pos;
+            if let Some(path) = game.route(entity, waypoints, world_pos) {
+                for position in path {
                     graphics.board.set_char_background(
                         position.x,
                         position.y,
-                        DARKEST_RED,
+                        DARKER_GREEN,
                         BackgroundFlag::Set
                     );
                 }
             }
-        },
-
-        PlayerState::Building(entity) => {
-            let unit = game.units.get(entity).unwrap();
-            invert_cell(&mut graphics.board, unit.position);
 
-            if unit.actions != 0 {
-                let action_circle = ActionCircle::new(unit.position, 1, None, &game.board);
-                for (position, _) in action_circle {    
-                    graphics.board.set_char_background(
-                        position.x,
-                        position.y,
-                        DARKEST_GREEN,
-                        BackgroundFlag::Set
-                    );
-                }
+            for position in waypoints {
+                graphics.board.set_char_background(
+                    position.x,
+                    position.y,
+                    DARK_AZURE,
+                    BackgroundFlag::Set
+                );
             }
-        },
-
-        _ => {
+        } else {
+            for position in ability.ability().targets(game, entity) {
+                graphics.board.set_char_background(
+                    position.x,
+                    position.y,
+                    ability_color(ability),
+                    BackgroundFlag::Set
+                );
+            }
+        }
 
+        // Preview the blast of an exploding unit aimed at the hovered tile.
+        let world_pos = input.mouse().world_pos;
+        let centre    = match ability {
+            AbilityKind::Attack if unit.has_special(Special::Explode) => Some(world_pos)
+                .filter(|at| ability.ability().validate(game, entity, *at, 0)),
+            AbilityKind::Detonate                                     => Some(unit.position),
+            _                                                         => None
+        };
+
+        if let Some(centre) = centre {
+            for damage in unit.explosion.blast(game, unit.team, unit.damage, centre) {
+                graphics.board.set_char_background(
+                    damage.at.x,
+                    damage.at.y,
+                    DARK_ORANGE,
+                    BackgroundFlag::Set
+                );
+            }
         }
     }
 
@@ -374,8 +223,24 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
 
 
 
+    // Lay the board out on the console as its topology draws it.
+    let topology = board.topology();
+    graphics.layout.clear();
+    for y in 0..board.height() as i32 {
+        for x in 0..board.width() as i32 {
+            let cell = topology.to_screen(Position::new(x, y));
+            graphics.layout.put_char_ex(
+                cell.x,
+                cell.y,
+                graphics.board.get_char(x, y),
+                graphics.board.get_char_foreground(x, y),
+                graphics.board.get_char_background(x, y)
+            );
+        }
+    }
+
     blit(
-        &graphics.board, 
+        &graphics.layout, 
         (0, 0), 
         (0, 0), 
         &mut graphics.root, 
@@ -393,6 +258,17 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
     graphics.root.print(1, graphics.root.height() - 3, format!("{:?}'s turn", game.player));
     graphics.root.set_default_foreground(WHITE);
 
+    // Funds label.
+    graphics.root.set_default_foreground(GOLD);
+    graphics.root.print_ex(
+        graphics.root.width() - 2,
+        graphics.root.height() - 3,
+        BackgroundFlag::None,
+        TextAlignment::Right,
+        format!("${}", game.funds(game.player))
+    );
+    graphics.root.set_default_foreground(WHITE);
+
     // Arrow before the current-state label.
     graphics.root.set_char(
         1,
@@ -407,7 +283,7 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
     );
 
     // Current-state label.
-    match game.player_state {
+    match state {
         PlayerState::Selecting => {
             graphics.root.print(
                 2,
@@ -424,27 +300,19 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
             );
         },
 
-        PlayerState::Moving(_) => {
+        PlayerState::Using(_, ability) => {
             graphics.root.print(
                 2,
                 graphics.root.height() - 2,
-                "Moving"
+                ability_label(ability)
             );
         },
 
-        PlayerState::Attacking(_) => {
+        PlayerState::Replaying { step, length } => {
             graphics.root.print(
                 2,
                 graphics.root.height() - 2,
-                "Attacking"
-            );
-        },
-
-        PlayerState::Building(_) => {
-            graphics.root.print(
-                2,
-                graphics.root.height() - 2,
-                "Building"
+                format!("Replay {}/{}", step, length)
             );
         },
 
@@ -453,8 +321,30 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
         }
     }
 
+    // Hotkeys for the abilities of the selected unit.
+    if let PlayerState::Controlling(entity) | PlayerState::Using(entity, _) = state {
+        if let Some(unit) = game.units.get(entity) {
+            // There is only room for the keys themselves.
+            let hotkeys: String = game.definitions
+                .abilities(&unit.kind)
+                .iter()
+                .map(|ability| ability.hotkey())
+                .collect();
+
+            graphics.root.set_default_foreground(GREY);
+            graphics.root.print_ex(
+                graphics.root.width() - 2,
+                graphics.root.height() - 2,
+                BackgroundFlag::None,
+                TextAlignment::Right,
+                hotkeys
+            );
+            graphics.root.set_default_foreground(WHITE);
+        }
+    }
+
     // Health and Action Points.
-    if let Some(entity) = game.board.entity_at(world_pos) {
+    if let Some(entity) = game.board.entity_at(world_pos).filter(|_| can_see(world_pos)) {
         if let Some(unit) = game.units.get(entity) {
             graphics.root.set_default_foreground(unit.team.color());
             graphics.root.print(
@@ -468,26 +358,128 @@ fn draw(game: &Game, graphics: &mut Graphics, input: &Input) {
                 1,
                 2,
                 format!("HP {}/{}   AP {}/{}", unit.health, unit.health_max, unit.actions, unit.actions_max)
-            )
+            );
+
+            if unit.uses_fuel() {
+                graphics.root.print(
+                    1,
+                    3,
+                    format!("Fuel {}/{}", unit.fuel, unit.fuel_max)
+                );
+            }
+
+            if unit.capacity != 0 {
+                graphics.root.print_ex(
+                    graphics.root.width() - 2,
+                    3,
+                    BackgroundFlag::None,
+                    TextAlignment::Right,
+                    format!("Cargo {}/{}", unit.cargo.len(), unit.capacity)
+                );
+            }
+        }
+    }
+
+    // Predicted outcome of attacking the hovered unit.
+    if let PlayerState::Using(entity, AbilityKind::Attack) = state {
+        let target_entity = game.board
+            .entity_at(world_pos)
+            .filter(|_| can_see(world_pos))
+            .filter(|_| game.attack_range(entity).is_some_and(|range| range.contains(&world_pos)));
+
+        let prediction = target_entity
+            .filter(|target_entity| game.units[*target_entity].team != game.units[entity].team)
+            .filter(|target_entity| can_target(&game.units[entity], &game.units[*target_entity]))
+            .and_then(|target_entity| predict_attack(game, entity, target_entity));
+
+        if let Some(prediction) = prediction {
+            graphics.root.set_default_foreground(LIGHT_RED);
+            graphics.root.print(
+                1,
+                4,
+                format!("Deal {}{}", prediction.damage, if prediction.defender_destroyed() { " KO" } else { "" })
+            );
+
+            graphics.root.print_ex(
+                graphics.root.width() - 2,
+                4,
+                BackgroundFlag::None,
+                TextAlignment::Right,
+                format!("Take {}{}", prediction.counter, if prediction.attacker_destroyed() { " KO" } else { "" })
+            );
+            graphics.root.set_default_foreground(WHITE);
         }
     }
 
     graphics.root.flush();
 }
 
-fn read_input(game: &mut Game, graphics: &mut Graphics, input: &mut Input) {
-    input.update(game.board.size(), graphics.board_offset);
+fn read_input(game: &mut Game, state: &mut PlayerState, waypoints: &mut Vec<Position>, graphics: &mut Graphics, input: &mut Input) {
+    input.update(game.board.size(), game.board.topology(), graphics.board_offset);
+
+    // Waypoints only last while the unit is being moved.
+    if !matches!(*state, PlayerState::Using(_, AbilityKind::Move)) {
+        waypoints.clear();
+    }
 
     let world_pos = input.mouse().world_pos;
 
     if input.key(KeyCode::O).down {
-        if !game.next_turn() {
+        if !end_turn(game, state) {
             return;
         }
     }
 
+    if input.key(KeyCode::S).down {
+        match save_game(game, Path::new(QUICKSAVE_PATH)) {
+            Ok(())     => println!("[Save] Success ({})", QUICKSAVE_PATH),
+            Err(error) => println!("[Save] Failure ({:?})", error)
+        }
+    }
+
+    if input.key(KeyCode::L).down {
+        match load_game(Path::new(QUICKSAVE_PATH), game.definitions.clone()) {
+            Ok(mut loaded) => {
+                println!("[Load] Success ({})", QUICKSAVE_PATH);
+                if loaded.recording.is_none() {
+                    loaded.recording = Some(Replay::new(&loaded));
+                }
+
+                *game  = loaded;
+                *state = PlayerState::Selecting;
+                return;
+            },
+
+            Err(error) => println!("[Load] Failure ({:?})", error)
+        }
+    }
+
+    if input.key(KeyCode::U).down {
+        if undo(game) {
+            println!("[Undo] Success");
+            *state = PlayerState::Selecting;
+        } else {
+            println!("[Undo] Failure (nothing to undo)");
+        }
+
+        return;
+    }
+
+    if input.key(KeyCode::R).down {
+        match redo(game) {
+            Some(Ok(()))     => println!("[Redo] Success"),
+            Some(Err(error)) => println!("[Redo] Failure ({:?})", error),
+            None             => println!("[Redo] Failure (nothing to redo)")
+        }
+
+        *state = PlayerState::Selecting;
+        return;
+    }
+
     if input.key(KeyCode::Delete).down {
-        game.damage_queue.push(DamageAtPos::new(world_pos, 100));
+        let damage = DamageAtPos::new(world_pos, 100);
+        game.record(Command::Damage(damage.clone()));
+        game.damage_queue.push(damage);
     }
 
     if input.button(MouseButton::Right).down {
@@ -495,189 +487,242 @@ fn read_input(game: &mut Game, graphics: &mut Graphics, input: &mut Input) {
         return;
     }
 
-    match game.player_state {
+    match *state {
         PlayerState::Selecting => {
             if input.button(MouseButton::Left).down {
                 if let Some(entity) = game.board.entity_at(world_pos) {
                     let unit = game.units.get(entity).unwrap();
                     if unit.team == game.player {
-                        game.player_state = PlayerState::Controlling(entity);
+                        *state = PlayerState::Controlling(entity);
                     }
                 }
             }
         },
 
-        PlayerState::Controlling(entity) => {
+        PlayerState::Controlling(entity) | PlayerState::Using(entity, _) => {
             if input.key(KeyCode::Escape).down {
-                game.player_state = PlayerState::Selecting;
+                *state = PlayerState::Selecting;
                 return;
             }
 
-            if input.key(KeyCode::M).down {
-                game.player_state = PlayerState::Moving(entity);
-                return;
-            }
-
-            if input.key(KeyCode::A).down {
-                game.player_state = PlayerState::Attacking(entity);
-                return;
+            let unit = game.units.get(entity).unwrap();
+            for ability in game.definitions.abilities(&unit.kind) {
+                if input.key(KeyCode::from_char(ability.hotkey())).down {
+                    *state = PlayerState::Using(entity, ability);
+                    return;
+                }
             }
 
-            if input.key(KeyCode::B).down {
-                game.player_state = PlayerState::Building(entity);
-                return;
+            if let PlayerState::Using(_, ability) = *state {
+                use_ability(game, state, waypoints, graphics, input, entity, ability);
             }
         },
 
-        PlayerState::Moving(entity) => {
-            if input.key(KeyCode::Escape).down {
-                game.player_state = PlayerState::Selecting;
-                return;
-            }
+        _ => {
 
-            if input.key(KeyCode::A).down {
-                game.player_state = PlayerState::Attacking(entity);
-                return;
-            }
+        }
+    }
+}
 
-            if input.key(KeyCode::B).down {
-                game.player_state = PlayerState::Building(entity);
-                return;
-            }
+fn end_turn(game: &mut Game, state: &mut PlayerState) -> bool {
+    bring_out_your_dead(game);
 
-            if input.button(MouseButton::Left).down {
-                let intent = IntentToMove {
-                    entity,
-                    to: world_pos
-                };
-
-                let result = move_unit(game, intent);
-                match result {
-                    Ok(()) => {
-                        println!("[Move] Success");
-                        let unit = game.units.get(entity).unwrap();
-                        if unit.actions == 0 {
-                            game.player_state = PlayerState::Selecting;
-                        }
-                    },
-
-                    Err(error) => {
-                        println!("[Move] Failure ({:?})", error);
-                        match error {
-                            MoveError::UnitInvalid |
-                            MoveError::UnitExhausted => {
-                                game.player_state = PlayerState::Selecting;
-                            }
-
-                            _ => {
-
-                            }
-                        }
-                    }
-                }
-            }
-        },
+    if apply_intent(game, Intent::EndTurn).is_ok() {
+        *state = PlayerState::Selecting;
 
-        PlayerState::Attacking(entity) => {
-            if input.key(KeyCode::Escape).down {
-                game.player_state = PlayerState::Selecting;
-                return;
-            }
+        println!("{:?}'s turn!", game.player);
 
-            if input.key(KeyCode::M).down {
-                game.player_state = PlayerState::Moving(entity);
-                return;
-            }
+        true
+    } else {
+        *state = PlayerState::GameOver;
 
-            if input.key(KeyCode::B).down {
-                game.player_state = PlayerState::Building(entity);
-                return;
-            }
+        false
+    }
+}
 
-            if input.button(MouseButton::Left).down {
-                if let Some(target_entity) = game.board.entity_at(world_pos) {
-                    let intent = IntentToAttack {
-                        entity,
-                        target_entity
-                    };
-    
-                    let result = attack_with_unit(game, intent);
-                    match result {
-                        Ok(()) => {
-                            println!("[Attack] Success");
-                            let unit = game.units.get(entity).unwrap();
-                            if unit.actions == 0 {
-                                game.player_state = PlayerState::Selecting;
-                            }
-                        },
-    
-                        Err(error) => {
-                            println!("[Attack] Failure ({:?})", error);
-                            match error {
-                                AttackError::UnitInvalid |
-                                AttackError::UnitExhausted => {
-                                    game.player_state = PlayerState::Selecting;
-                                },
-    
-                                _ => {
-    
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        },
+fn computer_turn(game: &mut Game, state: &mut PlayerState, ai: &mut dyn Ai) {
+    let intent = ai.think(game);
+    println!("[AI] {:?}", intent);
 
-        PlayerState::Building(entity) => {
-            if input.key(KeyCode::Escape).down {
-                game.player_state = PlayerState::Selecting;
-                return;
+    if let Intent::EndTurn = intent {
+        end_turn(game, state);
+        return;
+    }
+
+    if let Err(error) = apply_intent(game, intent) {
+        println!("[AI] Failure ({:?})", error);
+    }
+
+    bring_out_your_dead(game);
+
+    // Give the players a chance to follow what the computer is doing.
+    thread::sleep(AI_STEP_DELAY);
+}
+
+fn game_over_screen(game: &Game, graphics: &mut Graphics, input: &mut Input) {
+    while !graphics.root.window_closed() {
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
+
+        let width  = graphics.root.width();
+        let height = graphics.root.height();
+
+        graphics.root.clear();
+        graphics.root.print_ex(width / 2, height / 2 - 1, BackgroundFlag::None, TextAlignment::Center, "Game Over");
+
+        match game.outcome {
+            Outcome::Winner(team) => {
+                graphics.root.set_default_foreground(team.color());
+                graphics.root.print_ex(width / 2, height / 2 + 1, BackgroundFlag::None, TextAlignment::Center, format!("{:?} wins!", team));
+                graphics.root.set_default_foreground(WHITE);
+            },
+
+            _ => {
+                graphics.root.print_ex(width / 2, height / 2 + 1, BackgroundFlag::None, TextAlignment::Center, "Draw");
             }
+        }
 
-            if input.key(KeyCode::M).down {
-                game.player_state = PlayerState::Moving(entity);
-                return;
+        graphics.root.flush();
+
+        if input.any_key_down().is_some() {
+            break;
+        }
+    }
+}
+
+/// Aims the ability at the clicked tile, asking which option to use
+/// first if it has several.
+fn use_ability(game: &mut Game, state: &mut PlayerState, waypoints: &mut Vec<Position>, graphics: &mut Graphics, input: &mut Input, entity: EntityIndex, kind: AbilityKind) {
+    let world_pos = input.mouse().world_pos;
+
+    let shift = input.key(KeyCode::Shift);
+    if kind == AbilityKind::Move && input.button(MouseButton::Left).down && (shift.down || shift.held) {
+        if game.route(entity, waypoints, world_pos).is_some() {
+            waypoints.push(world_pos);
+        }
+
+        return;
+    }
+
+    if !input.button(MouseButton::Left).down {
+        return;
+    }
+
+    let ability = kind.ability();
+    let choices = ability.choices(game, entity);
+    let choice  = if choices.len() > 1 {
+        match choice_menu(game, graphics, input, kind.name(), &choices) {
+            Some(choice) => choice,
+            None         => return
+        }
+    } else {
+        0
+    };
+
+    let intent = if kind == AbilityKind::Move {
+        let path = game.route(entity, waypoints, world_pos);
+        waypoints.clear();
+
+        Intent::Move(IntentToMove {
+            entity,
+            to: world_pos,
+            path
+        })
+    } else {
+        match ability.intent(game, entity, world_pos, choice) {
+            Some(intent) => intent,
+            None         => return
+        }
+    };
+
+    // Earlier orders become final once a new one is given.
+    bring_out_your_dead(game);
+    match apply_intent(game, intent) {
+        Ok(())     => println!("[{}] Success", kind.name()),
+        Err(error) => println!("[{}] Failure ({:?})", kind.name(), error)
+    }
+
+    if !ability.is_usable(game, entity) {
+        *state = PlayerState::Selecting;
+    }
+}
+
+fn choice_menu(game: &Game, graphics: &mut Graphics, input: &mut Input, prompt: &str, choices: &[String]) -> Option<usize> {
+    let mut builder = MenuBuilder::new()
+        .with_prompt(String::from(prompt));
+
+    for (i, choice) in choices.iter().enumerate() {
+        builder = builder.with_option(choice.clone(), i);
+    }
+
+    let menu = builder.build();
+
+    loop {
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
+        let result = menu.show(graphics, input);
+        match result {
+            MenuResult::Selected(item) => {
+                return Some(item);
             }
 
-            if input.key(KeyCode::A).down {
-                game.player_state = PlayerState::Attacking(entity);
-                return;
+            MenuResult::NoResponse => {
+
             }
 
-            if input.button(MouseButton::Left).down {
-                println!("Build!");
-                game.player_state = PlayerState::Selecting;
+            MenuResult::Cancel => {
+                return None;
             }
         }
-        
-        _ => {
+    }
+}
 
-        }
+/// The state label shown while the ability is being aimed.
+fn ability_label(ability: AbilityKind) -> &'static str {
+    match ability {
+        AbilityKind::Move      => "Moving",
+        AbilityKind::Attack    => "Attacking",
+        AbilityKind::Build     => "Building",
+        AbilityKind::Produce   => "Producing",
+        AbilityKind::Embark    => "Embarking",
+        AbilityKind::Disembark => "Disembarking",
+        AbilityKind::Heal      => "Healing",
+        AbilityKind::Detonate  => "Detonating"
+    }
+}
+
+/// The colour of the tiles the ability can be used on.
+fn ability_color(ability: AbilityKind) -> Color {
+    match ability {
+        AbilityKind::Move      => DARKEST_GREY,
+        AbilityKind::Attack    => DARKEST_RED,
+        AbilityKind::Build     => DARKEST_GREEN,
+        AbilityKind::Produce   => DARKEST_BLUE,
+        AbilityKind::Embark    => DARKEST_AMBER,
+        AbilityKind::Disembark => DARKEST_AMBER,
+        AbilityKind::Heal      => DARKEST_CHARTREUSE,
+        AbilityKind::Detonate  => DARKEST_RED
     }
 }
 
 fn spawn_menu(game: &mut Game, graphics: &mut Graphics, input: &mut Input, at: Position) {
+    // Menus only have ten slots, so the units are split by where they go
+    // and structures get a menu of their own.
     let builder = MenuBuilder::new()
-        .with_prompt(String::from("Spawn/Unit"))
-        .with_option(String::from("Engineer"), UnitKind::Engineer)
-        .with_option(String::from("Infantry"), UnitKind::Infantry)
-        .with_option(String::from("Humvee"),   UnitKind::Humvee)
-        .with_option(String::from("Tank"),     UnitKind::Tank)
-        .with_option(String::from("Missile"),  UnitKind::Missile)
-        .with_option(String::from("Flag"),     UnitKind::Flag)
-        .with_option(String::from("Barracks"), UnitKind::Barracks);
+        .with_prompt(String::from("Spawn"))
+        .with_option(String::from("Ground"),    Some(Space::Ground))
+        .with_option(String::from("Sea"),       Some(Space::Water))
+        .with_option(String::from("Air"),       Some(Space::Air))
+        .with_option(String::from("Structure"), None);
 
     let menu = builder.build();
 
-    let kind: UnitKind;
+    let group: Option<Space>;
 
     loop {
-        input.update(game.board.size(), graphics.board_offset);
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
         let result = menu.show(graphics, input);
         match result {
             MenuResult::Selected(item) => {
-                kind = item;
+                group = item;
                 break;
             }
 
@@ -691,7 +736,44 @@ fn spawn_menu(game: &mut Game, graphics: &mut Graphics, input: &mut Input, at: P
         }
     }
 
+    let mut builder = MenuBuilder::new()
+        .with_prompt(String::from("Spawn/Unit"));
 
+    let kinds = game.definitions
+        .all()
+        .filter(|definition| match group {
+            Some(space) => definition.class != UnitClass::Structure && definition.space == space,
+            None        => definition.class == UnitClass::Structure
+        })
+        .map(|definition| definition.kind.clone())
+        .take(10);
+
+    for kind in kinds {
+        builder = builder.with_option(format!("{:?}", kind), kind);
+    }
+
+    let menu = builder.build();
+
+    let kind: UnitKind;
+
+    loop {
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
+        let result = menu.show(graphics, input);
+        match result {
+            MenuResult::Selected(item) => {
+                kind = item;
+                break;
+            }
+
+            MenuResult::NoResponse => {
+
+            }
+
+            MenuResult::Cancel => {
+                return;
+            }
+        }
+    }
 
     let builder = MenuBuilder::new()
         .with_prompt(String::from("Spawn/Unit/Team"))
@@ -709,7 +791,7 @@ fn spawn_menu(game: &mut Game, graphics: &mut Graphics, input: &mut Input, at: P
     let team: Team;
 
     loop {
-        input.update(game.board.size(), graphics.board_offset);
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
         let result = menu.show(graphics, input);
         match result {
             MenuResult::Selected(item) => {
@@ -727,80 +809,195 @@ fn spawn_menu(game: &mut Game, graphics: &mut Graphics, input: &mut Input, at: P
         }
     }
 
-    let result = game.spawn(SpawnData::new(kind, team, at));
+    let data   = SpawnData::new(kind, team, at);
+    let result = game.spawn(data.clone());
     match result {
-        Ok(entity) => println!("[Spawn] Success (entity={:?})", entity),
+        Ok(entity) => {
+            println!("[Spawn] Success (entity={:?})", entity);
+            game.record(Command::Spawn(data));
+        },
+
         Err(error) => println!("[Spawn] Failure ({:?})", error)
     }
 }
 
-fn bring_out_your_dead(game: &mut Game) {
-    for damage in &game.damage_queue {
-        if let Some(entity) = game.board.entity_at(damage.at) {
-            if let Some(unit) = game.units.get_mut(entity) {
-                unit.health -= damage.amount.min(unit.health);
+fn init_graphics(game: &Game) -> Graphics {
+    let layout = game.board.topology().screen_size(game.board.size());
 
-                if unit.health == 0 {
-                    game.board.remove_at(unit.position);
-                }
-            }
-        }
+    Graphics {
+        root: Root::initializer()
+                .size(24, 20)
+                .title("A Starless Void")
+                .font("res/Font 16x16 Extended.png", FontLayout::AsciiInRow)
+                .init(),
+
+        board:        Offscreen::new(game.board.width() as i32, game.board.height() as i32),
+        layout:       Offscreen::new(layout.width as i32, layout.height as i32),
+        board_offset: Position::new((24 - layout.width as i32).max(0) / 2, 5)
     }
+}
+
+/// The victory conditions every game is played under.
+fn victory_conditions() -> Vec<Box<dyn VictoryCondition>> {
+    vec![Box::new(CaptureTheFlag::new(Elimination::Remove))]
+}
+
+/// Recreates the recorded game after the first `step` commands. The
+/// victory conditions are part of the recorded scenario.
+fn replay_game_at(replay: &Replay, definitions: &Definitions, step: usize) -> Result<Game, ReplayError> {
+    let mut game = replay.start(definitions.clone())?;
+    replay.play(&mut game, 0, step)?;
+
+    Ok(game)
+}
+
+/// Steps through a recorded match. Right steps forward, Left steps back
+/// and Escape quits.
+fn replay_viewer(replay: &Replay, definitions: Definitions) {
+    let length = replay.commands.len();
+    let mut step = 0;
+
+    let mut game = match replay_game_at(replay, &definitions, step) {
+        Ok(game) => game,
+
+        Err(error) => {
+            println!("[Replay] Failure ({:?})", error);
+            return;
+        }
+    };
+
+    let mut graphics = init_graphics(&game);
+    let mut input    = Input::new();
+
+    while !graphics.root.window_closed() {
+        draw(&game, None, PlayerState::Replaying { step, length }, &[], &mut graphics, &input);
+
+        input.update(game.board.size(), game.board.topology(), graphics.board_offset);
 
-    game.damage_queue.clear();
+        if input.key(KeyCode::Escape).down {
+            break;
+        }
+
+        let result = if input.key(KeyCode::Right).down && step < length {
+            step += 1;
+            replay.play(&mut game, step - 1, step)
+        } else if input.key(KeyCode::Left).down && step > 0 {
+            // Commands cannot be reverted, so rebuild the game from the start.
+            step -= 1;
+            replay_game_at(replay, &definitions, step).map(|rebuilt| game = rebuilt)
+        } else {
+            continue;
+        };
 
-    game.units.retain(|_, unit| {
-        unit.health != 0
-    });
+        match result {
+            Ok(())     => println!("[Replay] Step {}/{}", step, length),
+            Err(error) => {
+                println!("[Replay] Failure ({:?})", error);
+                break;
+            }
+        }
+    }
 }
 
 fn main() {
     println!("Hello, world!");
-    
-    let mut graphics = Graphics {
-        root: Root::initializer()
-                .size(24, 20)
-                .title("A Starless Void")
-                .font("res/Font 16x16 Extended.png", FontLayout::AsciiInRow)
-                .init(),
 
-        board:        Offscreen::new(10, 10),
-        board_offset: Position::new(7, 5)
+    let definitions = match load_definitions(Path::new(DEFINITIONS_PATH)) {
+        Ok(definitions) => definitions,
+
+        Err(errors) => {
+            for error in errors {
+                println!("[Definitions] {} (line {}): {}", error.entry, error.line, error.reason);
+            }
+
+            println!("Could not start. Failed to load unit definitions.");
+            return;
+        }
     };
-    
-    let mut input = Input::new();
 
-    let mut game = Game {
-        player_state: PlayerState::Selecting,
-        player:       Team::White,
-        damage_queue: Vec::new(),
-        units:        Arena::new(),
-        board:        Board::new(Dimension::new(10, 10))
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 2 && args[1] == "--replay" {
+        match load_replay(Path::new(&args[2])) {
+            Ok(replay) => replay_viewer(&replay, definitions),
+            Err(error) => println!("Could not start. Failed to load replay '{}' ({:?}).", args[2], error)
+        }
+
+        return;
+    }
+
+    let map_path = args.get(1).cloned().unwrap_or_else(|| String::from(DEFAULT_MAP_PATH));
+    let map = match load_map(Path::new(&map_path), &definitions) {
+        Ok(map) => map,
+
+        Err(error) => {
+            println!("Could not start. Failed to load map '{}' ({:?}).", map_path, error);
+            return;
+        }
+    };
+
+    // Computer players are created when their first turn comes up, so that
+    // loading a save with different controls takes effect.
+    let mut computers: HashMap<Team, Box<dyn Ai>> = HashMap::new();
+
+    let mut game = match map.into_game(definitions) {
+        Ok(game) => game,
+
+        Err(error) => {
+            println!("Could not start. Failed to place units ({:?}).", error);
+            return;
+        }
     };
 
-    game.spawn(SpawnData::new(UnitKind::Engineer, Team::Red,    Position::new(2, 2))).unwrap();
-    game.spawn(SpawnData::new(UnitKind::Infantry, Team::Blue,   Position::new(4, 1))).unwrap();
-    game.spawn(SpawnData::new(UnitKind::Infantry, Team::Blue,   Position::new(5, 2))).unwrap();
-    game.spawn(SpawnData::new(UnitKind::Humvee,   Team::Green,  Position::new(2, 7))).unwrap();
-    game.spawn(SpawnData::new(UnitKind::Tank,     Team::Yellow, Position::new(4, 6))).unwrap();
+    let mut graphics = init_graphics(&game);
+
+    let mut input = Input::new();
+
+    let mut state     = PlayerState::Selecting;
+    let mut waypoints = Vec::new();
+    let mut viewer    = None;
 
-    game.spawn(SpawnData::new(UnitKind::Barracks, Team::Red,    Position::new(2, 1))).unwrap();
+    game.victory_conditions = victory_conditions();
+    game.recording = Some(Replay::new(&game));
 
-    if !game.next_turn() {
+    if !end_turn(&mut game, &mut state) {
         println!("Could not start. No units on the battlefield.");
         return;
     }
 
     while !graphics.root.window_closed() {
-        draw(&game, &mut graphics, &input);
-        read_input(&mut game, &mut graphics, &mut input);
-        
-        bring_out_your_dead(&mut game);
+        // Show the board as the last human player saw it, so that the
+        // computer's turns do not lift the fog of war.
+        let computer = game.controls.get(&game.player) == Some(&Control::Computer);
+        if !computer {
+            viewer = Some(game.player);
+        }
+
+        draw(&game, viewer, state, &waypoints, &mut graphics, &input);
+
+        if computer {
+            let ai = computers.entry(game.player).or_insert_with(|| Box::new(GreedyAi::new()));
+            input.update(game.board.size(), game.board.topology(), graphics.board_offset);
+            computer_turn(&mut game, &mut state, ai.as_mut());
+        } else {
+            read_input(&mut game, &mut state, &mut waypoints, &mut graphics, &mut input);
+        }
+
+        if game.outcome != Outcome::Undecided {
+            state = PlayerState::GameOver;
+        }
 
-        if game.player_state == PlayerState::GameOver {
+        if state == PlayerState::GameOver {
+            game_over_screen(&game, &mut graphics, &mut input);
             break;
         }
     }
 
+    if let Some(replay) = &game.recording {
+        match save_replay(replay, Path::new(REPLAY_PATH)) {
+            Ok(())     => println!("[Replay] Saved ({})", REPLAY_PATH),
+            Err(error) => println!("[Replay] Failure ({:?})", error)
+        }
+    }
+
     println!("Goodbyte, world!");
 }
\ No newline at end of file
@@ -0,0 +1,354 @@
+use std::collections::{HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path};
+
+use crate::{Game, Board, Dimension, Position, Topology, TileKind, Team, UnitKind, SpawnData, SpawnError, Definitions, Control};
+
+/// A board and the units placed on it at the start of a game.
+///
+/// Map files are split into sections. The `[legend]` section assigns a
+/// tile kind to each symbol, the `[grid]` section draws the board one row
+/// per line, and the `[units]` section places units as `Kind Team X Y`.
+/// The optional `[players]` section hands teams to the computer as
+/// `Team Computer`; teams that are not listed are played by humans.
+/// The optional `[topology]` section holds `Square` or `Hex`. Hex boards
+/// use axial coordinates, so each row of the grid is drawn half a hex
+/// further east than the one above it. Lines starting with `;` are
+/// comments.
+///
+/// ```text
+/// [legend]
+/// # Wall
+/// . Floor
+/// ~ Ocean
+///
+/// [grid]
+/// #####
+/// #.~.#
+/// #####
+///
+/// [units]
+/// Infantry Red 1 1
+///
+/// [players]
+/// Red Computer
+/// ```
+#[derive(Debug)]
+pub struct Map {
+    pub board:    Board,
+    pub units:    Vec<SpawnData>,
+    pub controls: HashMap<Team, Control>
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Io(io::Error),
+    GridMissing,
+    Malformed { line: usize, reason: String },
+    SpawnFailed { line: usize, error: SpawnError }
+}
+
+impl From<io::Error> for MapError {
+    fn from(error: io::Error) -> Self {
+        MapError::Io(error)
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Section {
+    None,
+    Legend,
+    Grid,
+    Units,
+    Players,
+    Topology
+}
+
+impl Map {
+    /// Creates a game on the map's board with its starting units.
+    pub fn into_game(self, definitions: Definitions) -> Result<Game, SpawnError> {
+        let mut game = Game::new(self.board);
+        game.definitions = definitions;
+        game.controls    = self.controls;
+
+        for data in self.units {
+            game.spawn(data)?;
+        }
+
+        Ok(game)
+    }
+}
+
+pub fn load_map(path: &Path, definitions: &Definitions) -> Result<Map, MapError> {
+    let text = fs::read_to_string(path)?;
+    read_map(&text, definitions)
+}
+
+/// Parses a map, checking that every unit is defined and placed on
+/// terrain it can stand on.
+pub fn read_map(text: &str, definitions: &Definitions) -> Result<Map, MapError> {
+    let mut section = Section::None;
+    let mut legend  = HashMap::new();
+    let mut rows: Vec<Vec<TileKind>> = Vec::new();
+    let mut units   = Vec::new();
+    let mut controls = HashMap::new();
+    let mut topology = Topology::Square;
+
+    for (i, text) in text.lines().enumerate() {
+        let line = i + 1;
+        let malformed = |reason: String| MapError::Malformed { line, reason };
+
+        let trimmed = text.trim();
+        if trimmed.starts_with(';') || (trimmed.is_empty() && section != Section::Grid) {
+            continue;
+        }
+
+        match trimmed {
+            "[legend]"   => { section = Section::Legend;   continue; },
+            "[grid]"     => { section = Section::Grid;     continue; },
+            "[units]"    => { section = Section::Units;    continue; },
+            "[players]"  => { section = Section::Players;  continue; },
+            "[topology]" => { section = Section::Topology; continue; },
+            _            => {}
+        }
+
+        match section {
+            Section::None => {
+                return Err(malformed(String::from("entry outside of a section")));
+            },
+
+            Section::Legend => {
+                let mut chars = trimmed.chars();
+                let symbol    = chars.next().unwrap();
+                let name      = chars.as_str().trim();
+                let kind: TileKind = name.parse()
+                    .map_err(|_| malformed(format!("unknown tile kind '{}'", name)))?;
+
+                legend.insert(symbol, kind);
+            },
+
+            Section::Grid => {
+                if trimmed.is_empty() {
+                    section = Section::None;
+                    continue;
+                }
+
+                let mut row = Vec::new();
+                for symbol in text.trim_end().chars() {
+                    let kind = legend.get(&symbol)
+                        .ok_or_else(|| malformed(format!("symbol '{}' is not in the legend", symbol)))?;
+
+                    row.push(*kind);
+                }
+
+                if !rows.is_empty() && rows[0].len() != row.len() {
+                    return Err(malformed(String::from("row has a different width to the first row")));
+                }
+
+                rows.push(row);
+            },
+
+            Section::Units => {
+                let words: Vec<&str> = trimmed.split_whitespace().collect();
+                match words.as_slice() {
+                    [kind, team, x, y] => {
+                        let kind: UnitKind = kind.parse()
+                            .map_err(|_| malformed(format!("unknown unit kind '{}'", kind)))?;
+                        let team: Team = team.parse()
+                            .map_err(|_| malformed(format!("unknown team '{}'", team)))?;
+                        let x: i32 = x.parse()
+                            .map_err(|_| malformed(String::from("invalid x")))?;
+                        let y: i32 = y.parse()
+                            .map_err(|_| malformed(String::from("invalid y")))?;
+
+                        units.push((line, SpawnData::new(kind, team, Position::new(x, y))));
+                    },
+
+                    _ => {
+                        return Err(malformed(String::from("expected 'Kind Team X Y'")));
+                    }
+                }
+            },
+
+            Section::Players => {
+                let words: Vec<&str> = trimmed.split_whitespace().collect();
+                match words.as_slice() {
+                    [team, control] => {
+                        let team: Team = team.parse()
+                            .map_err(|_| malformed(format!("unknown team '{}'", team)))?;
+                        let control: Control = control.parse()
+                            .map_err(|_| malformed(format!("unknown control '{}'", control)))?;
+
+                        controls.insert(team, control);
+                    },
+
+                    _ => {
+                        return Err(malformed(String::from("expected 'Team Control'")));
+                    }
+                }
+            },
+
+            Section::Topology => {
+                topology = trimmed.parse()
+                    .map_err(|_| malformed(format!("unknown topology '{}'", trimmed)))?;
+            }
+        }
+    }
+
+    if rows.is_empty() {
+        return Err(MapError::GridMissing);
+    }
+
+    let size = Dimension::new(rows[0].len() as u32, rows.len() as u32);
+    let mut board = Board::new(size);
+    board.set_topology(topology);
+    for (y, row) in rows.iter().enumerate() {
+        for (x, kind) in row.iter().enumerate() {
+            board.set_tile_at(Position::new(x as i32, y as i32), *kind);
+        }
+    }
+
+    // Check the placements up front so errors can point at the line.
+    let mut occupied = Vec::new();
+    for (line, data) in &units {
+        let definition = definitions.get(&data.kind)
+            .ok_or(MapError::SpawnFailed { line: *line, error: SpawnError::KindUndefined })?;
+
+        let tile = board.tile_at(data.position)
+            .ok_or(MapError::SpawnFailed { line: *line, error: SpawnError::PositionOutOfBounds })?;
+
+        if !definition.space.can_traverse(tile.traverse()) {
+            return Err(MapError::SpawnFailed { line: *line, error: SpawnError::TerrainIncompatible });
+        }
+
+        if occupied.contains(&data.position) {
+            return Err(MapError::SpawnFailed { line: *line, error: SpawnError::PositionOccupied });
+        }
+
+        occupied.push(data.position);
+    }
+
+    Ok(Map {
+        board,
+        units: units.into_iter().map(|(_, data)| data).collect(),
+        controls
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGEND: &str = "\
+[legend]
+# Wall
+. Floor
+~ Ocean
+";
+
+    fn map(rest: &str) -> Result<Map, MapError> {
+        read_map(&format!("{}{}", LEGEND, rest), &Definitions::default())
+    }
+
+    #[test]
+    fn reads_the_board_units_and_players() {
+        let map = map("\
+[grid]
+#####
+#.~.#
+#####
+
+[units]
+Infantry Red 1 1
+Tank     Blue 3 1
+
+[players]
+Blue Computer
+").unwrap();
+
+        assert_eq!(map.board.size(), Dimension::new(5, 3));
+        assert!(map.board.tile_at(Position::new(2, 1)).unwrap().is_water());
+        assert_eq!(map.units.len(), 2);
+        assert_eq!(map.units[1].kind, UnitKind::TANK);
+        assert_eq!(map.units[1].team, Team::Blue);
+        assert_eq!(map.controls.get(&Team::Blue), Some(&Control::Computer));
+        assert!(!map.controls.contains_key(&Team::Red));
+        assert_eq!(map.board.topology(), Topology::Square);
+    }
+
+    #[test]
+    fn reads_the_topology() {
+        let map = map("[grid]\n...\n\n[topology]\nHex\n").unwrap();
+
+        assert_eq!(map.board.topology(), Topology::Hex);
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let cases = [
+            ("[grid]\n.x.\n",                            6),
+            ("[grid]\n...\n..\n",                        7),
+            ("[grid]\n...\n\n[units]\nTank Red 1\n",     9),
+            ("[grid]\n...\n\n[units]\nTank Mauve 1 0\n", 9),
+            ("[grid]\n...\n\n[players]\nRed Robot\n",    9)
+        ];
+
+        for (text, expected) in cases {
+            match map(text) {
+                Err(MapError::Malformed { line, .. }) => assert_eq!(line, expected, "{}", text),
+                other                                 => panic!("{:?} for {}", other.map(|_| ()), text)
+            }
+        }
+    }
+
+    #[test]
+    fn placements_are_checked_against_the_board() {
+        let grid = "[grid]\n#.~#\n\n[units]\n";
+        let cases = [
+            ("Tank Red 5 0",                   "out of bounds"),
+            ("Tank Red 2 0",                   "terrain"),
+            ("Gunboat Red 1 0",                "terrain"),
+            ("Tank Red 0 0",                   "terrain"),
+            ("Tank Red 1 0\nInfantry Red 1 0", "occupied"),
+            ("Walker Red 1 0",                 "undefined")
+        ];
+
+        for (units, reason) in cases {
+            let error = match map(&format!("{}{}\n", grid, units)) {
+                Err(MapError::SpawnFailed { error, .. }) => error,
+                other                                    => panic!("{:?} for {}", other.map(|_| ()), units)
+            };
+
+            let matches = match reason {
+                "out of bounds" => matches!(error, SpawnError::PositionOutOfBounds),
+                "terrain"       => matches!(error, SpawnError::TerrainIncompatible),
+                "occupied"      => matches!(error, SpawnError::PositionOccupied),
+                _               => matches!(error, SpawnError::KindUndefined)
+            };
+
+            assert!(matches, "{:?} for {}", error, units);
+        }
+    }
+
+    #[test]
+    fn failed_placements_report_their_line() {
+        match map("[grid]\n.~.\n\n[units]\nTank Red 0 0\nTank Red 1 0\n") {
+            Err(MapError::SpawnFailed { line, error: SpawnError::TerrainIncompatible }) => assert_eq!(line, 10),
+            other => panic!("{:?}", other.map(|_| ()))
+        }
+    }
+
+    #[test]
+    fn the_skirmish_map_gives_every_team_a_flag() {
+        let map = load_map(Path::new("res/Maps/Skirmish.map"), &Definitions::default()).unwrap();
+
+        for unit in &map.units {
+            let flagged = map.units
+                .iter()
+                .any(|other| other.team == unit.team && other.kind == UnitKind::FLAG);
+
+            assert!(flagged, "{:?} has no flag", unit.team);
+        }
+    }
+}
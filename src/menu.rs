@@ -1,28 +1,28 @@
 use tcod::console::*;
 use crate::{Graphics, Input, KeyCode};
 
-pub enum MenuResult<T: Copy> {
+pub enum MenuResult<T: Clone> {
     Selected(T),
     NoResponse,
     Cancel
 }
 
-pub struct MenuOption<T: Copy> {
+pub struct MenuOption<T: Clone> {
     text: String,
     item: T
 }
 
-pub struct Menu<T: Copy> {
+pub struct Menu<T: Clone> {
     pub prompt:  String,
     pub options: Vec<MenuOption<T>>
 }
 
-pub struct MenuBuilder<T: Copy> {
+pub struct MenuBuilder<T: Clone> {
     prompt:  String,
     options: Vec<MenuOption<T>>
 }
 
-impl<T: Copy> MenuBuilder<T> {
+impl<T: Clone> MenuBuilder<T> {
     pub fn new() -> Self {
         MenuBuilder {
             prompt:  String::from("Select an Option"),
@@ -52,7 +52,7 @@ impl<T: Copy> MenuBuilder<T> {
     }
 }
 
-impl<T: Copy> Menu<T> {
+impl<T: Clone> Menu<T> {
     pub fn show(&self, graphics: &mut Graphics, input: &Input) -> MenuResult<T> {
         graphics.root.clear();
         graphics.root.print(1, 1, format!("{}", self.prompt));
@@ -89,7 +89,7 @@ impl<T: Copy> Menu<T> {
                 let option = self.options.get(index);
                 match option {
                     Some(option) => {
-                        return MenuResult::Selected(option.item);
+                        return MenuResult::Selected(option.item.clone());
                     },
 
                     None => {
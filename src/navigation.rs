@@ -0,0 +1,49 @@
+use crate::{Dimension, Position, Topology};
+
+/// A grid of walkable flags used for pathfinding over the board.
+#[derive(Debug, Clone)]
+pub struct NavMap {
+    size:     Dimension,
+    topology: Topology,
+    walkable: Vec<bool>
+}
+
+impl NavMap {
+    pub fn new(size: Dimension, topology: Topology) -> Self {
+        NavMap {
+            size,
+            topology,
+            walkable: vec![false; size.area() as usize]
+        }
+    }
+
+    pub fn size(&self) -> Dimension {
+        self.size
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn set(&mut self, position: Position, walkable: bool) {
+        if let Some(index) = self.to_index(position) {
+            self.walkable[index] = walkable;
+        }
+    }
+
+    pub fn is_walkable(&self, position: Position) -> bool {
+        match self.to_index(position) {
+            Some(index) => self.walkable[index],
+            None        => false
+        }
+    }
+
+    fn to_index(&self, position: Position) -> Option<usize> {
+        if position.x >= 0 && position.x < self.size.width  as i32 &&
+           position.y >= 0 && position.y < self.size.height as i32 {
+            Some((position.x + position.y * self.size.width as i32) as usize)
+        } else {
+            None
+        }
+    }
+}
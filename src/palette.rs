@@ -0,0 +1,46 @@
+use tcod::colors::*;
+use emulation::{Team, Tile, TileKind};
+
+pub trait TeamColor {
+    fn color(&self) -> Color;
+}
+
+impl TeamColor for Team {
+    fn color(&self) -> Color {
+        match self {
+            Team::Red     => RED,
+            Team::Blue    => BLUE,
+            Team::Green   => GREEN,
+            Team::Yellow  => YELLOW,
+            Team::Cyan    => CYAN,
+            Team::Orange  => ORANGE,
+            Team::Magenta => MAGENTA,
+            Team::White   => WHITE
+        }
+    }
+}
+
+pub trait TileColor {
+    fn fore_color(&self) -> Color;
+    fn back_color(&self) -> Color;
+}
+
+impl TileColor for Tile {
+    fn fore_color(&self) -> Color {
+        match self.kind() {
+            TileKind::Floor  => DARK_GREY,
+            TileKind::Wall   => DARK_GREY,
+            TileKind::Ocean  => DARKER_BLUE,
+            TileKind::Forest => DARK_GREEN
+        }
+    }
+
+    fn back_color(&self) -> Color {
+        match self.kind() {
+            TileKind::Floor  => BLACK,
+            TileKind::Wall   => DARK_GREY,
+            TileKind::Ocean  => DARKEST_BLUE,
+            TileKind::Forest => BLACK
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::ops::{Add, Sub, Mul, Div};
 use std::fmt::{self, Display};
 
-use crate::{Dimension};
+use crate::{Dimension, Topology};
 
 #[derive(Debug, Default, PartialEq, Eq, Hash, Copy, Clone)]
 pub struct Position {
@@ -21,8 +21,37 @@ impl Position {
         ((self.x - other.x).abs() + (self.y - other.y).abs()) as u32
     }
 
-    pub fn into_world_pos(&self, world_size: Dimension, world_offset: Position) -> Self {
-        let world_pos = *self - world_offset;
+    pub fn chebyshev_distance(&self, other: &Self) -> u32 {
+        (self.x - other.x).unsigned_abs().max((self.y - other.y).unsigned_abs())
+    }
+
+    pub fn euclidean_distance(&self, other: &Self) -> f32 {
+        let dx = (self.x - other.x) as f32;
+        let dy = (self.y - other.y) as f32;
+
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// The steps between two positions in axial coordinates on a hex grid.
+    pub fn hex_distance(&self, other: &Self) -> u32 {
+        let dx = self.x - other.x;
+        let dy = self.y - other.y;
+
+        (dx.abs() + dy.abs() + (dx + dy).abs()) as u32 / 2
+    }
+
+    /// The four orthogonally adjacent positions.
+    pub fn neighbours(&self) -> [Position; 4] {
+        [
+            *self + Position::new( 0, -1),
+            *self + Position::new( 1,  0),
+            *self + Position::new( 0,  1),
+            *self + Position::new(-1,  0)
+        ]
+    }
+
+    pub fn into_world_pos(&self, world_size: Dimension, world_offset: Position, topology: Topology) -> Self {
+        let world_pos = topology.from_screen(*self - world_offset);
         world_pos.clamp_inside(
             0, 
             0, 
@@ -37,24 +66,6 @@ impl Position {
             y: self.y.max(y).min(y + h)
         }
     }
-
-    pub fn radius(&self, radius: i32) -> Vec<Position> {
-        let mut positions = Vec::new();
-        for y in -radius..=radius {
-            for x in -radius..=radius {
-                if x == 0 && y == 0 {
-                    continue;
-                }
-
-                let new_position = *self + Position::new(x as i32, y as i32);
-                if (*self).manhatten_distance(&new_position) <= radius as u32 {
-                    positions.push(new_position);
-                }
-            }
-        }
-
-        positions
-    }
 }
 
 impl Add for Position {
@@ -101,15 +112,15 @@ impl Div<i32> for Position {
     }
 }
 
-impl Into<(i32, i32)> for Position {
-    fn into(self) -> (i32, i32) {
-        (self.x, self.y)
+impl From<Position> for (i32, i32) {
+    fn from(position: Position) -> Self {
+        (position.x, position.y)
     }
 }
 
-impl Into<(u32, u32)> for Position {
-    fn into(self) -> (u32, u32) {
-        (self.x as u32, self.y as u32)
+impl From<Position> for (u32, u32) {
+    fn from(position: Position) -> Self {
+        (position.x as u32, position.y as u32)
     }
 }
 
@@ -0,0 +1,550 @@
+use std::fmt::{Write as FmtWrite};
+use std::fs;
+use std::io;
+use std::path::{Path};
+
+use crate::{Game, Position, Team, UnitKind, SpawnData, DamageAtPos, Definitions, LoadError};
+use crate::game::*;
+use crate::history::*;
+use crate::save::*;
+use crate::transport::*;
+use crate::healing::*;
+
+/// The version written to the header of every replay file.
+pub const REPLAY_VERSION: u32 = 1;
+
+const REPLAY_MAGIC: &str = "emulation-replay";
+
+/// Something that changed the game, in the order it happened.
+///
+/// Units are referred to by the tile they stood on when the command was
+/// given rather than by entity index, so a replay does not depend on how
+/// the arena of the recorded game was laid out.
+#[derive(Debug, Clone)]
+pub enum Command {
+    Move    { from: Position, to: Position, path: Option<Vec<Position>> },
+    Attack  { from: Position, target: Position },
+    Build   { from: Position, kind: UnitKind, at: Position },
+    Produce { from: Position, kind: UnitKind, at: Position },
+    Embark  { from: Position, transport: Position },
+
+    /// Cargo is referred to by its place in the transport's hold.
+    Disembark { from: Position, cargo: usize, at: Position },
+    Heal    { from: Position, patient: Position },
+    Detonate { from: Position },
+    EndTurn,
+    Undo,
+    Redo,
+
+    /// The damage queue was applied with `bring_out_your_dead`.
+    Resolve,
+
+    /// A unit placed outside of the rules, such as from the debug menu.
+    Spawn(SpawnData),
+
+    /// Damage queued outside of the rules.
+    Damage(DamageAtPos)
+}
+
+impl Command {
+    /// Describes the intent in terms of positions. Returns None if the
+    /// intent refers to units that do not exist.
+    pub fn from_intent(game: &Game, intent: &Intent) -> Option<Self> {
+        let position_of = |entity| game.units.get(entity).map(|unit| unit.position);
+
+        let command = match intent {
+            Intent::Move(intent) => Command::Move {
+                from: position_of(intent.entity)?,
+                to:   intent.to,
+                path: intent.path.clone()
+            },
+
+            Intent::Attack(intent) => Command::Attack {
+                from:   position_of(intent.entity)?,
+                target: position_of(intent.target_entity)?
+            },
+
+            Intent::Build(intent) => Command::Build {
+                from: position_of(intent.entity)?,
+                kind: intent.kind.clone(),
+                at:   intent.at
+            },
+
+            Intent::Produce(intent) => Command::Produce {
+                from: position_of(intent.entity)?,
+                kind: intent.kind.clone(),
+                at:   intent.at
+            },
+
+            Intent::Embark(intent) => Command::Embark {
+                from:      position_of(intent.entity)?,
+                transport: position_of(intent.transport)?
+            },
+
+            Intent::Disembark(intent) => Command::Disembark {
+                from:  position_of(intent.entity)?,
+                cargo: intent.cargo,
+                at:    intent.at
+            },
+
+            Intent::Heal(intent) => Command::Heal {
+                from:    position_of(intent.entity)?,
+                patient: position_of(intent.patient_entity)?
+            },
+
+            Intent::Detonate(intent) => Command::Detonate {
+                from: position_of(intent.entity)?
+            },
+
+            Intent::EndTurn => Command::EndTurn
+        };
+
+        Some(command)
+    }
+}
+
+/// A recording of a match: the game it started from and every command
+/// applied to it since.
+///
+/// The rules are deterministic, so applying the commands to the scenario
+/// in order reproduces the match exactly. Victory conditions are not part
+/// of the scenario and must be added again before playing it back.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub scenario: String,
+    pub commands: Vec<Command>
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    HeaderMissing,
+    VersionUnsupported(u32),
+    Malformed { line: usize, reason: String },
+    Scenario(LoadError),
+
+    /// A command could not be applied the way it was when recorded.
+    Desync { step: usize, reason: String }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl Replay {
+    /// Starts a recording from the current state of the game.
+    pub fn new(game: &Game) -> Self {
+        Replay {
+            scenario: write_game(game),
+            commands: Vec::new()
+        }
+    }
+
+    /// Creates the game the recording started from.
+    pub fn start(&self, definitions: Definitions) -> Result<Game, ReplayError> {
+        read_game(&self.scenario, definitions).map_err(ReplayError::Scenario)
+    }
+
+    /// Applies the commands from `from` up to, but not including, `to`.
+    pub fn play(&self, game: &mut Game, from: usize, to: usize) -> Result<(), ReplayError> {
+        for step in from..to.min(self.commands.len()) {
+            apply_command(game, &self.commands[step])
+                .map_err(|reason| ReplayError::Desync { step, reason })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies a recorded command. Intents that succeeded when they were
+/// recorded must succeed again, otherwise the replay has gone out of sync.
+pub fn apply_command(game: &mut Game, command: &Command) -> Result<(), String> {
+    let entity_at = |game: &Game, at: Position| {
+        game.board.entity_at(at).ok_or_else(|| format!("no unit at {:?}", at))
+    };
+
+    let intent = match command {
+        Command::Move { from, to, path } => Intent::Move(IntentToMove {
+            entity: entity_at(game, *from)?,
+            to:     *to,
+            path:   path.clone()
+        }),
+
+        Command::Attack { from, target } => Intent::Attack(IntentToAttack {
+            entity:        entity_at(game, *from)?,
+            target_entity: entity_at(game, *target)?
+        }),
+
+        Command::Build { from, kind, at } => Intent::Build(IntentToBuild {
+            entity: entity_at(game, *from)?,
+            kind:   kind.clone(),
+            at:     *at
+        }),
+
+        Command::Produce { from, kind, at } => Intent::Produce(IntentToProduce {
+            entity: entity_at(game, *from)?,
+            kind:   kind.clone(),
+            at:     *at
+        }),
+
+        Command::Embark { from, transport } => Intent::Embark(IntentToEmbark {
+            entity:    entity_at(game, *from)?,
+            transport: entity_at(game, *transport)?
+        }),
+
+        Command::Disembark { from, cargo, at } => Intent::Disembark(IntentToDisembark {
+            entity: entity_at(game, *from)?,
+            cargo:  *cargo,
+            at:     *at
+        }),
+
+        Command::Heal { from, patient } => Intent::Heal(IntentToHeal {
+            entity:         entity_at(game, *from)?,
+            patient_entity: entity_at(game, *patient)?
+        }),
+
+        Command::Detonate { from } => Intent::Detonate(IntentToDetonate {
+            entity: entity_at(game, *from)?
+        }),
+
+        Command::EndTurn => {
+            // Ending the last turn of a match fails once the outcome is
+            // decided, just as it did when it was recorded.
+            let _ = apply_intent(game, Intent::EndTurn);
+            return Ok(());
+        },
+
+        Command::Undo => {
+            return if undo(game) {
+                Ok(())
+            } else {
+                Err(String::from("nothing to undo"))
+            };
+        },
+
+        Command::Redo => {
+            // A redo that failed when recorded still used up the intent.
+            return match redo(game) {
+                Some(_) => Ok(()),
+                None    => Err(String::from("nothing to redo"))
+            };
+        },
+
+        Command::Resolve => {
+            bring_out_your_dead(game);
+            return Ok(());
+        },
+
+        Command::Spawn(data) => {
+            return game.spawn(data.clone())
+                .map(|_| ())
+                .map_err(|error| format!("{:?}", error));
+        },
+
+        Command::Damage(damage) => {
+            game.damage_queue.push(damage.clone());
+            return Ok(());
+        }
+    };
+
+    apply_intent(game, intent).map_err(|error| format!("{:?}", error))
+}
+
+pub fn save_replay(replay: &Replay, path: &Path) -> io::Result<()> {
+    fs::write(path, write_replay(replay))
+}
+
+pub fn load_replay(path: &Path) -> Result<Replay, ReplayError> {
+    let text = fs::read_to_string(path)?;
+    read_replay(&text)
+}
+
+/// Serialises the replay. The scenario is stored in the save format,
+/// followed by one command per line.
+///
+/// ```text
+/// emulation-replay 1
+/// [scenario]
+/// emulation-save 11
+/// ...
+/// [commands]
+/// move 2 2 3 3 path 2 3 3 3
+/// attack 3 3 4 3
+/// resolve
+/// end
+/// ```
+pub fn write_replay(replay: &Replay) -> String {
+    let mut text = String::new();
+
+    writeln!(text, "{} {}", REPLAY_MAGIC, REPLAY_VERSION).unwrap();
+    writeln!(text, "[scenario]").unwrap();
+    text.push_str(&replay.scenario);
+    writeln!(text, "[commands]").unwrap();
+
+    for command in &replay.commands {
+        match command {
+            Command::Move { from, to, path } => {
+                write!(text, "move {} {} {} {}", from.x, from.y, to.x, to.y).unwrap();
+                if let Some(path) = path {
+                    write!(text, " path").unwrap();
+                    for step in path {
+                        write!(text, " {} {}", step.x, step.y).unwrap();
+                    }
+                }
+
+                writeln!(text).unwrap();
+            },
+
+            Command::Attack { from, target } => {
+                writeln!(text, "attack {} {} {} {}", from.x, from.y, target.x, target.y).unwrap();
+            },
+
+            Command::Build { from, kind, at } => {
+                writeln!(text, "build {} {} {:?} {} {}", from.x, from.y, kind, at.x, at.y).unwrap();
+            },
+
+            Command::Produce { from, kind, at } => {
+                writeln!(text, "produce {} {} {:?} {} {}", from.x, from.y, kind, at.x, at.y).unwrap();
+            },
+
+            Command::Embark { from, transport } => {
+                writeln!(text, "embark {} {} {} {}", from.x, from.y, transport.x, transport.y).unwrap();
+            },
+
+            Command::Disembark { from, cargo, at } => {
+                writeln!(text, "disembark {} {} {} {} {}", from.x, from.y, cargo, at.x, at.y).unwrap();
+            },
+
+            Command::Heal { from, patient } => {
+                writeln!(text, "heal {} {} {} {}", from.x, from.y, patient.x, patient.y).unwrap();
+            },
+
+            Command::Detonate { from } => {
+                writeln!(text, "detonate {} {}", from.x, from.y).unwrap();
+            },
+
+            Command::EndTurn => writeln!(text, "end").unwrap(),
+            Command::Undo    => writeln!(text, "undo").unwrap(),
+            Command::Redo    => writeln!(text, "redo").unwrap(),
+            Command::Resolve => writeln!(text, "resolve").unwrap(),
+
+            Command::Spawn(data) => {
+                writeln!(text, "spawn {:?} {:?} {} {}", data.kind, data.team, data.position.x, data.position.y).unwrap();
+            },
+
+            Command::Damage(damage) => {
+                writeln!(text, "damage {} {} {}", damage.at.x, damage.at.y, damage.amount).unwrap();
+            }
+        }
+    }
+
+    text
+}
+
+/// Parses a replay. The scenario is only checked when the replay is
+/// started.
+pub fn read_replay(text: &str) -> Result<Replay, ReplayError> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let (_, header) = lines.next().ok_or(ReplayError::HeaderMissing)?;
+    let mut words   = header.split_whitespace();
+    if words.next() != Some(REPLAY_MAGIC) {
+        return Err(ReplayError::HeaderMissing);
+    }
+
+    let version = words.next()
+        .and_then(|word| word.parse().ok())
+        .ok_or(ReplayError::HeaderMissing)?;
+
+    if version != REPLAY_VERSION {
+        return Err(ReplayError::VersionUnsupported(version));
+    }
+
+    match lines.next() {
+        Some((_, "[scenario]")) => {},
+        Some((line, _))         => return Err(ReplayError::Malformed { line, reason: String::from("expected '[scenario]'") }),
+        None                    => return Err(ReplayError::Malformed { line: 1, reason: String::from("scenario is missing") })
+    }
+
+    let mut scenario = String::new();
+    loop {
+        match lines.next() {
+            Some((_, "[commands]")) => break,
+            Some((_, text))         => { scenario.push_str(text); scenario.push('\n'); },
+            None                    => return Err(ReplayError::Malformed { line: 0, reason: String::from("commands are missing") })
+        }
+    }
+
+    let mut commands = Vec::new();
+
+    for (line, text) in lines {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let malformed = |reason: &str| ReplayError::Malformed { line, reason: String::from(reason) };
+
+        let number = |word: &str| word.parse::<i32>().map_err(|_| malformed("invalid coordinate"));
+        let kind   = |word: &str| word.parse::<UnitKind>().map_err(|_| malformed("unknown unit kind"));
+
+        let command = match words.as_slice() {
+            [] => continue,
+
+            ["move", fx, fy, tx, ty, rest @ ..] => {
+                let path = match rest {
+                    [] => None,
+
+                    ["path", steps @ ..] if steps.len() % 2 == 0 => {
+                        let mut path = Vec::new();
+                        for step in steps.chunks(2) {
+                            path.push(Position::new(number(step[0])?, number(step[1])?));
+                        }
+
+                        Some(path)
+                    },
+
+                    _ => return Err(malformed("expected 'path' followed by pairs of coordinates"))
+                };
+
+                Command::Move {
+                    from: Position::new(number(fx)?, number(fy)?),
+                    to:   Position::new(number(tx)?, number(ty)?),
+                    path
+                }
+            },
+
+            ["attack", fx, fy, tx, ty] => Command::Attack {
+                from:   Position::new(number(fx)?, number(fy)?),
+                target: Position::new(number(tx)?, number(ty)?)
+            },
+
+            ["build", fx, fy, k, x, y] => Command::Build {
+                from: Position::new(number(fx)?, number(fy)?),
+                kind: kind(k)?,
+                at:   Position::new(number(x)?, number(y)?)
+            },
+
+            ["produce", fx, fy, k, x, y] => Command::Produce {
+                from: Position::new(number(fx)?, number(fy)?),
+                kind: kind(k)?,
+                at:   Position::new(number(x)?, number(y)?)
+            },
+
+            ["embark", fx, fy, tx, ty] => Command::Embark {
+                from:      Position::new(number(fx)?, number(fy)?),
+                transport: Position::new(number(tx)?, number(ty)?)
+            },
+
+            ["disembark", fx, fy, cargo, x, y] => Command::Disembark {
+                from:  Position::new(number(fx)?, number(fy)?),
+                cargo: cargo.parse().map_err(|_| malformed("invalid cargo"))?,
+                at:    Position::new(number(x)?, number(y)?)
+            },
+
+            ["heal", fx, fy, tx, ty] => Command::Heal {
+                from:    Position::new(number(fx)?, number(fy)?),
+                patient: Position::new(number(tx)?, number(ty)?)
+            },
+
+            ["detonate", x, y] => Command::Detonate {
+                from: Position::new(number(x)?, number(y)?)
+            },
+
+            ["end"]     => Command::EndTurn,
+            ["undo"]    => Command::Undo,
+            ["redo"]    => Command::Redo,
+            ["resolve"] => Command::Resolve,
+
+            ["spawn", k, team, x, y] => {
+                let team: Team = team.parse().map_err(|_| malformed("unknown team"))?;
+                Command::Spawn(SpawnData::new(kind(k)?, team, Position::new(number(x)?, number(y)?)))
+            },
+
+            ["damage", x, y, amount] => {
+                let amount = amount.parse().map_err(|_| malformed("invalid amount"))?;
+                Command::Damage(DamageAtPos::new(Position::new(number(x)?, number(y)?), amount))
+            },
+
+            _ => return Err(malformed("unrecognised command"))
+        };
+
+        commands.push(command);
+    }
+
+    Ok(Replay {
+        scenario,
+        commands
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension};
+
+    fn recorded_game() -> Game {
+        let mut game = Game::new(Board::new(Dimension::new(10, 6)));
+        game.player = Team::Red;
+        game.funds.insert(Team::Red, 300);
+        game.rules.fog_of_war = false;
+
+        game.spawn(SpawnData::new(UnitKind::TANK,     Team::Red,  Position::new(2, 2))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::FACTORY,  Team::Red,  Position::new(1, 4))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Blue, Position::new(5, 2))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::TANK,     Team::Blue, Position::new(8, 4))).unwrap();
+
+        game.recording = Some(Replay::new(&game));
+        game
+    }
+
+    fn play(game: &mut Game, command: Command) {
+        apply_command(game, &command).unwrap();
+    }
+
+    #[test]
+    fn replaying_a_recording_reproduces_the_game() {
+        let mut game = recorded_game();
+
+        play(&mut game, Command::Move { from: Position::new(2, 2), to: Position::new(3, 2), path: None });
+        play(&mut game, Command::Attack { from: Position::new(3, 2), target: Position::new(5, 2) });
+        play(&mut game, Command::Resolve);
+        play(&mut game, Command::Produce { from: Position::new(1, 4), kind: UnitKind::TANK, at: Position::new(2, 4) });
+        play(&mut game, Command::Undo);
+        play(&mut game, Command::Redo);
+        play(&mut game, Command::EndTurn);
+        play(&mut game, Command::Move { from: Position::new(8, 4), to: Position::new(7, 3), path: None });
+        play(&mut game, Command::EndTurn);
+
+        let replay = game.recording.clone().unwrap();
+        assert_eq!(replay.commands.len(), 9);
+
+        let mut replayed = replay.start(Definitions::default()).unwrap();
+        replay.play(&mut replayed, 0, replay.commands.len()).unwrap();
+
+        assert_eq!(write_game(&replayed), write_game(&game));
+    }
+
+    #[test]
+    fn replay_files_round_trip() {
+        let mut game = recorded_game();
+        play(&mut game, Command::Move { from: Position::new(2, 2), to: Position::new(2, 1), path: Some(vec![Position::new(2, 1)]) });
+        play(&mut game, Command::Damage(DamageAtPos::new(Position::new(5, 2), 1)));
+        play(&mut game, Command::Resolve);
+
+        let replay = game.recording.unwrap();
+        let text   = write_replay(&replay);
+
+        assert_eq!(write_replay(&read_replay(&text).unwrap()), text);
+    }
+
+    #[test]
+    fn commands_that_no_longer_apply_are_a_desync() {
+        let mut replay = recorded_game().recording.unwrap();
+        replay.commands.push(Command::EndTurn);
+        replay.commands.push(Command::Attack { from: Position::new(4, 4), target: Position::new(5, 2) });
+
+        let mut replayed = replay.start(Definitions::default()).unwrap();
+        let result       = replay.play(&mut replayed, 0, replay.commands.len());
+
+        assert!(matches!(result, Err(ReplayError::Desync { step: 1, .. })));
+    }
+}
@@ -0,0 +1,560 @@
+use std::fmt::{Write as FmtWrite};
+use std::fs;
+use std::io;
+use std::path::{Path};
+
+use crate::{Game, Board, Dimension, Position, Topology, TileKind, Team, Unit, UnitKind, SpawnData, DamageAtPos, HealingAtPos, Outcome, Definitions, EntityIndex, Control, ReplayError, Visibility};
+use crate::victory::{read_victory_condition};
+use crate::replay::{read_replay, write_replay};
+
+/// The version written to the header of every save file. Bump this when
+/// the format changes so that older files are rejected instead of being
+/// misread.
+pub const SAVE_VERSION: u32 = 11;
+
+const SAVE_MAGIC: &str = "emulation-save";
+
+/// Separates the game from its recording in a save file.
+const RECORDING_MARKER: &str = "[recording]";
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    HeaderMissing,
+    VersionUnsupported(u32),
+    Malformed { line: usize, reason: String },
+
+    /// The recording that follows the game could not be read.
+    Recording(Box<ReplayError>)
+}
+
+impl From<io::Error> for LoadError {
+    fn from(error: io::Error) -> Self {
+        LoadError::Io(error)
+    }
+}
+
+/// Writes the game to a file, including its rules, who controls each team
+/// and the victory conditions with their progress. Unit definitions are
+/// not saved; they belong to the scenario and are supplied again on load.
+///
+/// If the game is being recorded, the recording follows the game so that
+/// a loaded game carries on with the replay of the match so far.
+pub fn save_game(game: &Game, path: &Path) -> io::Result<()> {
+    let mut text = write_game(game);
+    if let Some(recording) = &game.recording {
+        writeln!(text, "{}", RECORDING_MARKER).unwrap();
+        text.push_str(&write_replay(recording));
+    }
+
+    fs::write(path, text)
+}
+
+pub fn load_game(path: &Path, definitions: Definitions) -> Result<Game, LoadError> {
+    let text = fs::read_to_string(path)?;
+    let (text, recording) = match text.split_once(&format!("\n{}\n", RECORDING_MARKER)) {
+        Some((text, recording)) => (text, Some(recording)),
+        None                    => (text.as_str(), None)
+    };
+
+    let mut game = read_game(text, definitions)?;
+    if let Some(recording) = recording {
+        let recording = read_replay(recording).map_err(|error| LoadError::Recording(Box::new(error)))?;
+        game.recording = Some(recording);
+    }
+
+    Ok(game)
+}
+
+/// Serialises the game into the save format.
+///
+/// Units are written in arena order, so the entity indices of a loaded
+/// game will differ from the saved one. Each unit keeps its id, which
+/// `Game::entity_with_id` maps to its new index. Carried units follow
+/// their transport on `cargo` lines.
+pub fn write_game(game: &Game) -> String {
+    let mut text = String::new();
+
+    writeln!(text, "{} {}", SAVE_MAGIC, SAVE_VERSION).unwrap();
+    writeln!(text, "player {:?}", game.player).unwrap();
+    writeln!(text, "turn {}", game.turn).unwrap();
+    writeln!(text, "next_id {}", game.next_id).unwrap();
+
+    match game.outcome {
+        Outcome::Undecided    => writeln!(text, "outcome undecided").unwrap(),
+        Outcome::Winner(team) => writeln!(text, "outcome winner {:?}", team).unwrap(),
+        Outcome::Draw         => writeln!(text, "outcome draw").unwrap()
+    }
+
+    let mut funds: Vec<(&Team, &u32)> = game.funds.iter().collect();
+    funds.sort_by_key(|(team, _)| **team as u8);
+    for (team, amount) in funds {
+        writeln!(text, "funds {:?} {}", team, amount).unwrap();
+    }
+
+    let rules = [
+        ("pass_through_friendlies", game.rules.pass_through_friendlies),
+        ("fog_of_war",              game.rules.fog_of_war),
+        ("counterattacks",          game.rules.counterattacks),
+        ("eject_cargo",             game.rules.eject_cargo)
+    ];
+
+    for (rule, enabled) in rules {
+        writeln!(text, "rule {} {}", rule, enabled).unwrap();
+    }
+
+    let mut controls: Vec<(&Team, &Control)> = game.controls.iter().collect();
+    controls.sort_by_key(|(team, _)| **team as u8);
+    for (team, control) in controls {
+        writeln!(text, "control {:?} {:?}", team, control).unwrap();
+    }
+
+    for condition in &game.victory_conditions {
+        writeln!(text, "victory {}", condition.write()).unwrap();
+    }
+
+    writeln!(text, "board {} {}", game.board.width(), game.board.height()).unwrap();
+    for y in 0..game.board.height() {
+        let row: String = (0..game.board.width())
+            .map(|x| game.board.tile_at(Position::new(x as i32, y as i32)).unwrap().kind().symbol())
+            .collect();
+
+        writeln!(text, "{}", row).unwrap();
+    }
+
+    writeln!(text, "topology {:?}", game.board.topology()).unwrap();
+
+    // What each team has explored is written like the board, with a 1 for
+    // every explored tile. What it can see now follows from its units.
+    let mut visibility: Vec<(&Team, &Visibility)> = game.visibility.iter().collect();
+    visibility.sort_by_key(|(team, _)| **team as u8);
+    for (team, visibility) in visibility {
+        writeln!(text, "explored {:?}", team).unwrap();
+        for y in 0..game.board.height() {
+            let row: String = (0..game.board.width())
+                .map(|x| if visibility.is_explored(Position::new(x as i32, y as i32)) { '1' } else { '0' })
+                .collect();
+
+            writeln!(text, "{}", row).unwrap();
+        }
+    }
+
+    for (_, unit) in &game.units {
+        writeln!(
+            text,
+            "unit {} {:?} {:?} {} {} health {} actions {} fuel {}",
+            unit.id,
+            unit.kind,
+            unit.team,
+            unit.position.x,
+            unit.position.y,
+            unit.health,
+            unit.actions,
+            unit.fuel
+        ).unwrap();
+
+        for cargo in &unit.cargo {
+            writeln!(
+                text,
+                "cargo {} {:?} {:?} health {} actions {} fuel {}",
+                cargo.id,
+                cargo.kind,
+                cargo.team,
+                cargo.health,
+                cargo.actions,
+                cargo.fuel
+            ).unwrap();
+        }
+    }
+
+    for damage in &game.damage_queue {
+        writeln!(text, "damage {} {} {}", damage.at.x, damage.at.y, damage.amount).unwrap();
+    }
+
+    for healing in &game.healing_queue {
+        match healing.cargo {
+            Some(cargo) => writeln!(text, "healing {} {} {} cargo {}", healing.at.x, healing.at.y, healing.amount, cargo),
+            None        => writeln!(text, "healing {} {} {}", healing.at.x, healing.at.y, healing.amount)
+        }.unwrap();
+    }
+
+    text
+}
+
+/// Parses a game from the save format.
+pub fn read_game(text: &str, definitions: Definitions) -> Result<Game, LoadError> {
+    let mut lines = text.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let (_, header) = lines.next().ok_or(LoadError::HeaderMissing)?;
+    let mut words   = header.split_whitespace();
+    if words.next() != Some(SAVE_MAGIC) {
+        return Err(LoadError::HeaderMissing);
+    }
+
+    let version = words.next()
+        .and_then(|word| word.parse().ok())
+        .ok_or(LoadError::HeaderMissing)?;
+
+    if version != SAVE_VERSION {
+        return Err(LoadError::VersionUnsupported(version));
+    }
+
+    let mut game = Game::new(Board::new(Dimension::new(0, 0)));
+    game.definitions = definitions;
+
+    // Cargo is loaded onto the unit read before it.
+    let mut last_unit: Option<EntityIndex> = None;
+
+    // Spawning the units hands out ids, so the saved counter is restored
+    // once they are all placed.
+    let mut next_id = 0;
+
+    while let Some((line, text)) = lines.next() {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let malformed = |reason: &str| LoadError::Malformed { line, reason: String::from(reason) };
+
+        match words.as_slice() {
+            [] => {
+
+            },
+
+            ["player", team] => {
+                game.player = team.parse().map_err(|_| malformed("unknown team"))?;
+            },
+
+            ["turn", turn] => {
+                game.turn = turn.parse().map_err(|_| malformed("invalid turn"))?;
+            },
+
+            ["next_id", id] => {
+                next_id = id.parse().map_err(|_| malformed("invalid id"))?;
+            },
+
+            ["outcome", "undecided"] => {
+                game.outcome = Outcome::Undecided;
+            },
+
+            ["outcome", "winner", team] => {
+                game.outcome = Outcome::Winner(team.parse().map_err(|_| malformed("unknown team"))?);
+            },
+
+            ["outcome", "draw"] => {
+                game.outcome = Outcome::Draw;
+            },
+
+            ["funds", team, amount] => {
+                let team   = team.parse().map_err(|_| malformed("unknown team"))?;
+                let amount = amount.parse().map_err(|_| malformed("invalid amount"))?;
+                game.funds.insert(team, amount);
+            },
+
+            ["rule", rule, enabled] => {
+                let enabled = enabled.parse().map_err(|_| malformed("rule must be true or false"))?;
+                match *rule {
+                    "pass_through_friendlies" => game.rules.pass_through_friendlies = enabled,
+                    "fog_of_war"              => game.rules.fog_of_war              = enabled,
+                    "counterattacks"          => game.rules.counterattacks          = enabled,
+                    "eject_cargo"             => game.rules.eject_cargo             = enabled,
+                    _                         => return Err(malformed("unknown rule"))
+                }
+            },
+
+            ["control", team, control] => {
+                let team    = team.parse().map_err(|_| malformed("unknown team"))?;
+                let control = control.parse().map_err(|_| malformed("unknown control"))?;
+                game.controls.insert(team, control);
+            },
+
+            ["victory", condition @ ..] => {
+                let condition = read_victory_condition(condition).ok_or_else(|| malformed("invalid victory condition"))?;
+                game.victory_conditions.push(condition);
+            },
+
+            ["board", width, height] => {
+                let width  = width.parse().map_err(|_| malformed("invalid width"))?;
+                let height = height.parse().map_err(|_| malformed("invalid height"))?;
+                if width == 0 || height == 0 {
+                    return Err(malformed("board must not be empty"));
+                }
+
+                game.board = Board::new(Dimension::new(width, height));
+
+                for y in 0..height {
+                    let (line, row) = lines.next().ok_or_else(|| malformed("board is missing rows"))?;
+                    let row: Vec<char> = row.chars().collect();
+                    if row.len() != width as usize {
+                        return Err(LoadError::Malformed { line, reason: String::from("row has the wrong width") });
+                    }
+
+                    for (x, symbol) in row.into_iter().enumerate() {
+                        let kind = TileKind::from_symbol(symbol)
+                            .ok_or_else(|| LoadError::Malformed { line, reason: format!("unknown tile '{}'", symbol) })?;
+
+                        game.board.set_tile_at(Position::new(x as i32, y as i32), kind);
+                    }
+                }
+            },
+
+            ["explored", team] => {
+                let team: Team = team.parse().map_err(|_| malformed("unknown team"))?;
+                let size       = game.board.size();
+                if size.area() == 0 {
+                    return Err(malformed("explored tiles before the board"));
+                }
+
+                let mut visibility = Visibility::new(size);
+                for y in 0..size.height {
+                    let (line, row) = lines.next().ok_or_else(|| malformed("explored tiles are missing rows"))?;
+                    let row: Vec<char> = row.chars().collect();
+                    if row.len() != size.width as usize {
+                        return Err(LoadError::Malformed { line, reason: String::from("row has the wrong width") });
+                    }
+
+                    for (x, symbol) in row.into_iter().enumerate() {
+                        match symbol {
+                            '1' => visibility.explore(Position::new(x as i32, y as i32)),
+                            '0' => {},
+                            _   => return Err(LoadError::Malformed { line, reason: format!("unknown explored state '{}'", symbol) })
+                        }
+                    }
+                }
+
+                game.visibility.insert(team, visibility);
+            },
+
+            ["topology", topology] => {
+                let topology: Topology = topology.parse().map_err(|_| malformed("unknown topology"))?;
+                game.board.set_topology(topology);
+            },
+
+            ["unit", id, kind, team, x, y, "health", health, "actions", actions, "fuel", fuel] => {
+                let id: u32        = id.parse().map_err(|_| malformed("invalid id"))?;
+                let kind: UnitKind = kind.parse().map_err(|_| malformed("unknown unit kind"))?;
+                let team: Team     = team.parse().map_err(|_| malformed("unknown team"))?;
+                let x              = x.parse().map_err(|_| malformed("invalid x"))?;
+                let y              = y.parse().map_err(|_| malformed("invalid y"))?;
+
+                let entity = game.spawn(SpawnData::new(kind, team, Position::new(x, y)))
+                    .map_err(|error| malformed(&format!("cannot place unit ({:?})", error)))?;
+
+                let unit     = &mut game.units[entity];
+                unit.id      = id;
+                unit.health  = health.parse().map_err(|_| malformed("invalid health"))?;
+                unit.actions = actions.parse().map_err(|_| malformed("invalid actions"))?;
+                unit.fuel    = fuel.parse().map_err(|_| malformed("invalid fuel"))?;
+
+                last_unit = Some(entity);
+            },
+
+            ["cargo", id, kind, team, "health", health, "actions", actions, "fuel", fuel] => {
+                let id: u32        = id.parse().map_err(|_| malformed("invalid id"))?;
+                let kind: UnitKind = kind.parse().map_err(|_| malformed("unknown unit kind"))?;
+                let team: Team     = team.parse().map_err(|_| malformed("unknown team"))?;
+
+                let definition = game.definitions.get(&kind).ok_or_else(|| malformed("undefined unit kind"))?;
+                let mut unit   = Unit::new(definition, team, Position::new(0, 0));
+                unit.id        = id;
+                unit.health    = health.parse().map_err(|_| malformed("invalid health"))?;
+                unit.actions   = actions.parse().map_err(|_| malformed("invalid actions"))?;
+                unit.fuel      = fuel.parse().map_err(|_| malformed("invalid fuel"))?;
+
+                let transport = last_unit
+                    .and_then(|entity| game.units.get_mut(entity))
+                    .ok_or_else(|| malformed("cargo without a transport"))?;
+
+                unit.position = transport.position;
+                transport.cargo.push(unit);
+            },
+
+            ["damage", x, y, amount] => {
+                let x      = x.parse().map_err(|_| malformed("invalid x"))?;
+                let y      = y.parse().map_err(|_| malformed("invalid y"))?;
+                let amount = amount.parse().map_err(|_| malformed("invalid amount"))?;
+
+                game.damage_queue.push(DamageAtPos::new(Position::new(x, y), amount));
+            },
+
+            ["healing", x, y, amount] => {
+                let x      = x.parse().map_err(|_| malformed("invalid x"))?;
+                let y      = y.parse().map_err(|_| malformed("invalid y"))?;
+                let amount = amount.parse().map_err(|_| malformed("invalid amount"))?;
+
+                game.healing_queue.push(HealingAtPos::new(Position::new(x, y), amount));
+            },
+
+            ["healing", x, y, amount, "cargo", cargo] => {
+                let x      = x.parse().map_err(|_| malformed("invalid x"))?;
+                let y      = y.parse().map_err(|_| malformed("invalid y"))?;
+                let amount = amount.parse().map_err(|_| malformed("invalid amount"))?;
+                let cargo  = cargo.parse().map_err(|_| malformed("invalid cargo"))?;
+
+                game.healing_queue.push(HealingAtPos::aboard(Position::new(x, y), cargo, amount));
+            },
+
+            _ => {
+                return Err(malformed("unrecognised entry"));
+            }
+        }
+    }
+
+    game.next_id = next_id;
+
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, Replay, CaptureTheFlag, Elimination, HoldStructures};
+
+    fn sample_game() -> Game {
+        let mut game = Game::new(Board::new(Dimension::new(6, 5)));
+        game.player = Team::Blue;
+        game.turn   = 7;
+        game.funds.insert(Team::Blue, 350);
+        game.rules.counterattacks = false;
+        game.controls.insert(Team::Red, Control::Computer);
+        game.board.set_tile_at(Position::new(2, 2), TileKind::Ocean);
+
+        let humvee = game.spawn(SpawnData::new(UnitKind::HUMVEE, Team::Blue, Position::new(1, 1))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::TANK,     Team::Red,  Position::new(3, 1))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::FLAG,     Team::Blue, Position::new(5, 4))).unwrap();
+        game.spawn(SpawnData::new(UnitKind::BARRACKS, Team::Blue, Position::new(0, 4))).unwrap();
+        game.units[humvee].health  = 1;
+        game.units[humvee].actions = 1;
+
+        let definition = game.definitions.get(&UnitKind::INFANTRY).unwrap();
+        let mut cargo  = Unit::new(definition, Team::Blue, Position::new(1, 1));
+        cargo.id       = 7;
+        cargo.health   = 1;
+        game.units[humvee].cargo.push(cargo);
+        game.next_id = 8;
+
+        game.victory_conditions.push(Box::new(CaptureTheFlag::new(Elimination::Convert)));
+        game.victory_conditions.push(Box::new(HoldStructures::new(1, 3)));
+        game.check_victory();
+
+        game.damage_queue.push(DamageAtPos::new(Position::new(3, 1), 2));
+        game.healing_queue.push(HealingAtPos::aboard(Position::new(1, 1), 0, 1));
+
+        game
+    }
+
+    #[test]
+    fn round_trip_keeps_the_game() {
+        let game   = sample_game();
+        let saved  = write_game(&game);
+        let loaded = read_game(&saved, Definitions::default()).unwrap();
+
+        assert_eq!(write_game(&loaded), saved);
+        assert_eq!(loaded.player, Team::Blue);
+        assert_eq!(loaded.funds(Team::Blue), 350);
+        assert!(!loaded.rules.counterattacks);
+        assert!(loaded.rules.fog_of_war);
+        assert_eq!(loaded.controls.get(&Team::Red), Some(&Control::Computer));
+        assert_eq!(loaded.board.tile_at(Position::new(2, 2)).unwrap().kind(), TileKind::Ocean);
+
+        let humvee = loaded.board.entity_at(Position::new(1, 1)).unwrap();
+        assert_eq!(loaded.units[humvee].health, 1);
+        assert_eq!(loaded.units[humvee].actions, 1);
+        assert_eq!(loaded.units[humvee].cargo[0].health, 1);
+        assert_eq!(loaded.healing_queue[0].cargo, Some(0));
+    }
+
+    #[test]
+    fn explored_tiles_are_kept() {
+        let mut game = Game::new(Board::new(Dimension::new(8, 1)));
+        game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Red, Position::new(0, 0))).unwrap();
+        game.visibility.get_mut(&Team::Red).unwrap().explore(Position::new(7, 0));
+
+        let loaded = read_game(&write_game(&game), Definitions::default()).unwrap();
+
+        assert!(loaded.has_explored(Team::Red, Position::new(7, 0)));
+        assert!(!loaded.can_see(Team::Red, Position::new(7, 0)));
+        assert!(!loaded.has_explored(Team::Red, Position::new(6, 0)));
+        assert!(loaded.can_see(Team::Red, Position::new(0, 0)));
+    }
+
+    #[test]
+    fn units_keep_their_ids() {
+        let game   = sample_game();
+        let loaded = read_game(&write_game(&game), Definitions::default()).unwrap();
+
+        let tank = loaded.entity_with_id(1).unwrap();
+        assert_eq!(loaded.units[tank].kind, UnitKind::TANK);
+        assert_eq!(loaded.units[tank].position, Position::new(3, 1));
+
+        let humvee = loaded.entity_with_id(0).unwrap();
+        assert_eq!(loaded.units[humvee].cargo[0].id, 7);
+        assert_eq!(loaded.next_id, 8);
+    }
+
+    #[test]
+    fn victory_conditions_keep_their_progress() {
+        let game  = sample_game();
+        let saved = write_game(&game);
+        assert!(saved.contains("victory capture_the_flag Convert Blue\n"));
+        assert!(saved.contains("victory hold_structures 1 3 7 Blue 1\n"));
+
+        let mut loaded = read_game(&saved, Definitions::default()).unwrap();
+        assert_eq!(loaded.victory_conditions.len(), 2);
+
+        // The flag was held when the game was saved, so losing it now
+        // hands the team's units over.
+        let flag = loaded.board.entity_at(Position::new(5, 4)).unwrap();
+        loaded.board.remove_at(Position::new(5, 4));
+        loaded.units.remove(flag);
+        loaded.player = Team::Red;
+
+        assert_eq!(loaded.check_victory(), Outcome::Winner(Team::Red));
+    }
+
+    #[test]
+    fn saves_carry_the_recording() {
+        let mut game   = sample_game();
+        let mut replay = Replay::new(&game);
+        replay.commands.push(Command::Move { from: Position::new(1, 1), to: Position::new(1, 2), path: None });
+        replay.commands.push(Command::EndTurn);
+        game.recording = Some(replay);
+
+        let path = std::env::temp_dir().join(format!("emulation-save-test-{}", std::process::id()));
+        save_game(&game, &path).unwrap();
+        let loaded = load_game(&path, Definitions::default());
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(write_game(&loaded), write_game(&game));
+        assert_eq!(
+            loaded.recording.as_ref().map(write_replay),
+            game.recording.as_ref().map(write_replay)
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions_and_bad_entries() {
+        let load = |text: &str| read_game(text, Definitions::default());
+
+        assert!(matches!(load("not a save"), Err(LoadError::HeaderMissing)));
+        assert!(matches!(load("emulation-save 0\n"), Err(LoadError::VersionUnsupported(0))));
+
+        let header = format!("{} {}\n", SAVE_MAGIC, SAVE_VERSION);
+        let cases  = [
+            ("board 2 1\n.x\n",                                                3),
+            ("board 0 3\n",                                                    2),
+            ("board 2 1\n..\nunit 0 Tank Red 5 5 health 1 actions 1 fuel 0\n", 4),
+            ("cargo 0 Tank Red health 1 actions 1 fuel 0\n",                  2),
+            ("victory capture_the_flag Remove Purple\n",                      2),
+            ("explored Red\n",                                                 2),
+            ("board 2 1\n..\nexplored Red\n1x\n",                              5),
+            ("player Purple\n",                                                2),
+            ("unknown entry\n",                                                2)
+        ];
+
+        for (body, expected) in cases {
+            match load(&format!("{}{}", header, body)) {
+                Err(LoadError::Malformed { line, .. }) => assert_eq!(line, expected, "{}", body),
+                other                                  => panic!("{:?} for {}", other.map(|_| ()), body)
+            }
+        }
+    }
+}
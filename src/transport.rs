@@ -0,0 +1,240 @@
+use crate::{EntityIndex, Game, Position, Unit};
+
+#[derive(Debug, Clone)]
+pub struct IntentToEmbark {
+    /// The unit boarding the transport.
+    pub entity:    EntityIndex,
+    pub transport: EntityIndex
+}
+
+#[derive(Debug)]
+pub enum EmbarkError {
+    UnitInvalid,
+    UnitExhausted,
+    UnitCannotBoard,
+    TransportInvalid,
+    TransportFull,
+    TransportOutOfRange
+}
+
+/// Checks that the unit can board the transport.
+pub fn validate_embark(game: &Game, intent: &IntentToEmbark) -> Result<(), EmbarkError> {
+    if intent.entity == intent.transport {
+        return Err(EmbarkError::TransportInvalid);
+    }
+
+    let unit      = game.units.get(intent.entity).ok_or(EmbarkError::UnitInvalid)?;
+    let transport = game.units.get(intent.transport).ok_or(EmbarkError::TransportInvalid)?;
+
+    if unit.actions == 0 {
+        return Err(EmbarkError::UnitExhausted);
+    }
+
+    if transport.capacity == 0 {
+        return Err(EmbarkError::TransportInvalid);
+    }
+
+    if game.board.distance(unit.position, transport.position) != 1 {
+        return Err(EmbarkError::TransportOutOfRange);
+    }
+
+    if transport.cargo.len() as u32 >= transport.capacity {
+        return Err(EmbarkError::TransportFull);
+    }
+
+    if !unit.can_board(transport) {
+        return Err(EmbarkError::UnitCannotBoard);
+    }
+
+    Ok(())
+}
+
+/// Puts a unit aboard an adjacent transport. Boarding uses up the rest of
+/// the unit's actions, and it stays aboard until the transport unloads it.
+pub fn embark_unit(game: &mut Game, intent: IntentToEmbark) -> Result<(), EmbarkError> {
+    validate_embark(game, &intent)?;
+
+    let mut unit = game.units.remove(intent.entity).unwrap();
+    game.board.remove_at(unit.position);
+
+    let transport = &mut game.units[intent.transport];
+    unit.position = transport.position;
+    unit.actions  = 0;
+    transport.cargo.push(unit);
+
+    game.update_visibility();
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct IntentToDisembark {
+    pub entity: EntityIndex,
+
+    /// Which of the carried units to unload.
+    pub cargo:  usize,
+    pub at:     Position
+}
+
+#[derive(Debug)]
+pub enum DisembarkError {
+    UnitInvalid,
+    UnitExhausted,
+    CargoInvalid,
+    TerrainIncompatible,
+    PositionOutOfRange,
+    PositionOccupied
+}
+
+/// Checks that the transport can put the unit down on the tile.
+pub fn validate_disembark(game: &Game, intent: &IntentToDisembark) -> Result<(), DisembarkError> {
+    let transport = game.units.get(intent.entity).ok_or(DisembarkError::UnitInvalid)?;
+
+    if transport.actions == 0 {
+        return Err(DisembarkError::UnitExhausted);
+    }
+
+    let cargo = transport.cargo.get(intent.cargo).ok_or(DisembarkError::CargoInvalid)?;
+
+    if game.board.distance(transport.position, intent.at) != 1 {
+        return Err(DisembarkError::PositionOutOfRange);
+    }
+
+    let tile = game.board.tile_at(intent.at).ok_or(DisembarkError::PositionOutOfRange)?;
+    if !cargo.space.can_traverse(tile.traverse()) {
+        return Err(DisembarkError::TerrainIncompatible);
+    }
+
+    if game.board.entity_at(intent.at).is_some() {
+        return Err(DisembarkError::PositionOccupied);
+    }
+
+    Ok(())
+}
+
+/// Puts one of the transport's units down on an adjacent tile. This costs
+/// the transport an action, and the disembarked unit cannot act until its
+/// next turn.
+pub fn disembark_unit(game: &mut Game, intent: IntentToDisembark) -> Result<EntityIndex, DisembarkError> {
+    validate_disembark(game, &intent)?;
+
+    let transport = &mut game.units[intent.entity];
+    transport.actions -= 1;
+
+    let mut unit  = transport.cargo.remove(intent.cargo);
+    unit.position = intent.at;
+    unit.actions  = 0;
+
+    let disembarked = game.units.insert(unit);
+    game.board.insert_at(intent.at, disembarked);
+    game.update_visibility();
+
+    Ok(disembarked)
+}
+
+/// Takes the cargo off a destroyed transport, placing each unit on the
+/// transport's tile or the nearest free tile around it that it can stand
+/// on. Units with nowhere to go are lost with the transport.
+pub fn eject_cargo(game: &mut Game, cargo: Vec<Unit>, at: Position) {
+    let mut sites = vec![at];
+    sites.extend(game.board.neighbours(at).filter(|site| game.board.in_bounds(*site)));
+
+    for mut unit in cargo {
+        let site = sites.iter().copied().find(|site| {
+            game.board.entity_at(*site).is_none()
+                && game.board.tile_at(*site).is_some_and(|tile| unit.space.can_traverse(tile.traverse()))
+        });
+
+        if let Some(site) = site {
+            unit.position = site;
+            let entity = game.units.insert(unit);
+            game.board.insert_at(site, entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, Team, TileKind, UnitKind, SpawnData, DamageAtPos, bring_out_your_dead};
+
+    /// A strip of sea along the bottom of the board with a Transport at
+    /// (2, 3) and Infantry on the shore above it.
+    fn harbour() -> (Game, EntityIndex, EntityIndex) {
+        let mut game = Game::new(Board::new(Dimension::new(6, 5)));
+        for x in 0..6 {
+            game.board.set_tile_at(Position::new(x, 3), TileKind::Ocean);
+            game.board.set_tile_at(Position::new(x, 4), TileKind::Ocean);
+        }
+
+        let transport = game.spawn(SpawnData::new(UnitKind::TRANSPORT, Team::Red, Position::new(2, 3))).unwrap();
+        let infantry  = game.spawn(SpawnData::new(UnitKind::INFANTRY,  Team::Red, Position::new(2, 2))).unwrap();
+
+        (game, transport, infantry)
+    }
+
+    #[test]
+    fn units_board_and_leave_transports() {
+        let (mut game, transport, infantry) = harbour();
+
+        embark_unit(&mut game, IntentToEmbark { entity: infantry, transport }).unwrap();
+        assert!(!game.units.contains(infantry));
+        assert!(game.board.entity_at(Position::new(2, 2)).is_none());
+        assert_eq!(game.units[transport].cargo.len(), 1);
+
+        let at_sea = disembark_unit(&mut game, IntentToDisembark { entity: transport, cargo: 0, at: Position::new(3, 3) });
+        assert!(matches!(at_sea, Err(DisembarkError::TerrainIncompatible)));
+
+        let landed = disembark_unit(&mut game, IntentToDisembark { entity: transport, cargo: 0, at: Position::new(2, 2) }).unwrap();
+        assert_eq!(game.board.entity_at(Position::new(2, 2)), Some(landed));
+        assert_eq!(game.units[landed].kind, UnitKind::INFANTRY);
+        assert_eq!(game.units[landed].actions, 0);
+        assert!(game.units[transport].cargo.is_empty());
+    }
+
+    #[test]
+    fn structures_and_enemies_cannot_board() {
+        let mut game = Game::new(Board::new(Dimension::new(5, 5)));
+        let humvee   = game.spawn(SpawnData::new(UnitKind::HUMVEE,   Team::Red,  Position::new(2, 2))).unwrap();
+        let barracks = game.spawn(SpawnData::new(UnitKind::BARRACKS, Team::Red,  Position::new(1, 2))).unwrap();
+        let enemy    = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Blue, Position::new(3, 2))).unwrap();
+        let tank     = game.spawn(SpawnData::new(UnitKind::TANK,     Team::Red,  Position::new(2, 1))).unwrap();
+        let infantry = game.spawn(SpawnData::new(UnitKind::INFANTRY, Team::Red,  Position::new(2, 3))).unwrap();
+
+        let structure = embark_unit(&mut game, IntentToEmbark { entity: barracks, transport: humvee });
+        assert!(matches!(structure, Err(EmbarkError::UnitCannotBoard)));
+
+        let hostile = embark_unit(&mut game, IntentToEmbark { entity: enemy, transport: humvee });
+        assert!(matches!(hostile, Err(EmbarkError::UnitCannotBoard)));
+
+        embark_unit(&mut game, IntentToEmbark { entity: tank, transport: humvee }).unwrap();
+
+        let full = embark_unit(&mut game, IntentToEmbark { entity: infantry, transport: humvee });
+        assert!(matches!(full, Err(EmbarkError::TransportFull)));
+    }
+
+    #[test]
+    fn cargo_is_ejected_from_a_destroyed_transport() {
+        let (mut game, transport, infantry) = harbour();
+        embark_unit(&mut game, IntentToEmbark { entity: infantry, transport }).unwrap();
+
+        game.damage_queue.push(DamageAtPos::new(Position::new(2, 3), 10));
+        bring_out_your_dead(&mut game);
+
+        assert!(!game.units.contains(transport));
+        let survivor = game.board.entity_at(Position::new(2, 2)).unwrap();
+        assert_eq!(game.units[survivor].kind, UnitKind::INFANTRY);
+    }
+
+    #[test]
+    fn cargo_is_lost_without_the_rule() {
+        let (mut game, transport, infantry) = harbour();
+        embark_unit(&mut game, IntentToEmbark { entity: infantry, transport }).unwrap();
+        game.rules.eject_cargo = false;
+
+        game.damage_queue.push(DamageAtPos::new(Position::new(2, 3), 10));
+        bring_out_your_dead(&mut game);
+
+        assert!(game.units.is_empty());
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug};
+use std::str::{FromStr};
+
+use crate::{Game, Team, UnitClass, Special};
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Outcome {
+    Undecided,
+    Winner(Team),
+    Draw
+}
+
+/// What happens to the units of a team that has been eliminated.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Elimination {
+    /// The units are removed from the board.
+    Remove,
+
+    /// The units are handed over to the team whose turn it is.
+    Convert
+}
+
+impl FromStr for Elimination {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "Remove"  => Ok(Elimination::Remove),
+            "Convert" => Ok(Elimination::Convert),
+            _         => Err(())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Verdict {
+    Continue,
+    Eliminate(Vec<Team>, Elimination),
+    Winner(Team),
+    Draw
+}
+
+/// A rule that can eliminate teams or end the game. Conditions are
+/// evaluated whenever units are destroyed and at the start of every turn.
+pub trait VictoryCondition: Debug {
+    fn evaluate(&mut self, game: &Game) -> Verdict;
+
+    /// The condition and its progress so far as a line of the save format,
+    /// starting with the name `read_victory_condition` knows it by.
+    fn write(&self) -> String;
+}
+
+/// Recreates a condition from the words written by
+/// `VictoryCondition::write`.
+pub fn read_victory_condition(words: &[&str]) -> Option<Box<dyn VictoryCondition>> {
+    match words {
+        ["capture_the_flag", elimination, owners @ ..] => {
+            let mut condition = CaptureTheFlag::new(elimination.parse().ok()?);
+            for team in owners {
+                condition.flag_owners.insert(team.parse().ok()?);
+            }
+
+            Some(Box::new(condition))
+        },
+
+        ["hold_structures", count, turns, last_turn, held @ ..] => {
+            let mut condition = HoldStructures::new(count.parse().ok()?, turns.parse().ok()?);
+            condition.last_turn = last_turn.parse().ok()?;
+
+            if held.len() % 2 != 0 {
+                return None;
+            }
+
+            for pair in held.chunks(2) {
+                condition.held.insert(pair[0].parse().ok()?, pair[1].parse().ok()?);
+            }
+
+            Some(Box::new(condition))
+        },
+
+        ["turn_limit", turns] => Some(Box::new(TurnLimit::new(turns.parse().ok()?))),
+
+        _ => None
+    }
+}
+
+/// A team whose Flag is destroyed is eliminated.
+#[derive(Debug)]
+pub struct CaptureTheFlag {
+    elimination: Elimination,
+    flag_owners: HashSet<Team>
+}
+
+impl CaptureTheFlag {
+    pub fn new(elimination: Elimination) -> Self {
+        CaptureTheFlag {
+            elimination,
+            flag_owners: HashSet::new()
+        }
+    }
+}
+
+impl VictoryCondition for CaptureTheFlag {
+    fn evaluate(&mut self, game: &Game) -> Verdict {
+        let mut with_flag = HashSet::new();
+        for (_, unit) in &game.units {
+            if unit.has_special(Special::Objective) {
+                with_flag.insert(unit.team);
+            }
+        }
+
+        let lost: Vec<Team> = self.flag_owners
+            .difference(&with_flag)
+            .copied()
+            .collect();
+
+        self.flag_owners = with_flag;
+
+        if lost.is_empty() {
+            Verdict::Continue
+        } else {
+            Verdict::Eliminate(lost, self.elimination)
+        }
+    }
+
+    fn write(&self) -> String {
+        let mut owners: Vec<Team> = self.flag_owners.iter().copied().collect();
+        owners.sort_by_key(|team| *team as u8);
+
+        let mut text = format!("capture_the_flag {:?}", self.elimination);
+        for team in owners {
+            text.push_str(&format!(" {:?}", team));
+        }
+
+        text
+    }
+}
+
+/// A team wins by controlling at least `count` structures at the start of
+/// `turns` of its own consecutive turns.
+#[derive(Debug)]
+pub struct HoldStructures {
+    count:     usize,
+    turns:     u32,
+    last_turn: u32,
+    held:      HashMap<Team, u32>
+}
+
+impl HoldStructures {
+    pub fn new(count: usize, turns: u32) -> Self {
+        HoldStructures {
+            count,
+            turns,
+            last_turn: 0,
+            held:      HashMap::new()
+        }
+    }
+}
+
+impl VictoryCondition for HoldStructures {
+    fn evaluate(&mut self, game: &Game) -> Verdict {
+        if game.turn == self.last_turn {
+            return Verdict::Continue;
+        }
+
+        self.last_turn = game.turn;
+
+        let structures = game.units
+            .iter()
+            .filter(|(_, unit)| unit.team == game.player && unit.class == UnitClass::Structure)
+            .count();
+
+        let held = self.held.entry(game.player).or_insert(0);
+        if structures >= self.count {
+            *held += 1;
+        } else {
+            *held = 0;
+        }
+
+        if *held >= self.turns {
+            Verdict::Winner(game.player)
+        } else {
+            Verdict::Continue
+        }
+    }
+
+    fn write(&self) -> String {
+        let mut held: Vec<(&Team, &u32)> = self.held.iter().collect();
+        held.sort_by_key(|(team, _)| **team as u8);
+
+        let mut text = format!("hold_structures {} {} {}", self.count, self.turns, self.last_turn);
+        for (team, turns) in held {
+            text.push_str(&format!(" {:?} {}", team, turns));
+        }
+
+        text
+    }
+}
+
+/// The game ends after a number of turns. The team with the most units
+/// wins, or the game is drawn if several teams are tied.
+#[derive(Debug)]
+pub struct TurnLimit {
+    turns: u32
+}
+
+impl TurnLimit {
+    pub fn new(turns: u32) -> Self {
+        TurnLimit {
+            turns
+        }
+    }
+}
+
+impl VictoryCondition for TurnLimit {
+    fn evaluate(&mut self, game: &Game) -> Verdict {
+        if game.turn <= self.turns {
+            return Verdict::Continue;
+        }
+
+        let mut counts: HashMap<Team, u32> = HashMap::new();
+        for (_, unit) in &game.units {
+            *counts.entry(unit.team).or_insert(0) += 1 + unit.cargo.len() as u32;
+        }
+
+        let best = counts.values().copied().max().unwrap_or(0);
+        let mut leaders = counts
+            .iter()
+            .filter(|(_, count)| **count == best)
+            .map(|(team, _)| *team);
+
+        match (leaders.next(), leaders.next()) {
+            (Some(team), None) => Verdict::Winner(team),
+            _                  => Verdict::Draw
+        }
+    }
+
+    fn write(&self) -> String {
+        format!("turn_limit {}", self.turns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Board, Dimension, EntityIndex, Position, SpawnData, UnitKind};
+
+    fn game_with(conditions: Vec<Box<dyn VictoryCondition>>) -> Game {
+        let mut game = Game::new(Board::new(Dimension::new(8, 8)));
+        game.player             = Team::Red;
+        game.victory_conditions = conditions;
+        game
+    }
+
+    fn spawn(game: &mut Game, kind: UnitKind, team: Team, x: i32, y: i32) -> EntityIndex {
+        game.spawn(SpawnData::new(kind, team, Position::new(x, y))).unwrap()
+    }
+
+    fn destroy(game: &mut Game, entity: EntityIndex) {
+        game.board.remove_at(game.units[entity].position);
+        game.units.remove(entity);
+    }
+
+    #[test]
+    fn losing_the_flag_eliminates_the_team() {
+        let mut game = game_with(vec![Box::new(CaptureTheFlag::new(Elimination::Remove))]);
+        spawn(&mut game, UnitKind::FLAG, Team::Red,   1, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Green, 6, 6);
+        let flag = spawn(&mut game, UnitKind::FLAG, Team::Blue, 5, 1);
+        let tank = spawn(&mut game, UnitKind::TANK, Team::Blue, 5, 2);
+
+        assert_eq!(game.check_victory(), Outcome::Undecided);
+
+        destroy(&mut game, flag);
+
+        assert_eq!(game.check_victory(), Outcome::Undecided);
+        assert!(game.units.get(tank).is_none());
+        assert!(game.board.entity_at(Position::new(5, 2)).is_none());
+        assert!(game.units.iter().all(|(_, unit)| unit.team != Team::Blue));
+    }
+
+    #[test]
+    fn captured_units_join_the_team_whose_turn_it_is() {
+        let mut game = game_with(vec![Box::new(CaptureTheFlag::new(Elimination::Convert))]);
+        spawn(&mut game, UnitKind::FLAG, Team::Red, 1, 1);
+        let flag = spawn(&mut game, UnitKind::FLAG, Team::Blue, 5, 1);
+        let tank = spawn(&mut game, UnitKind::TANK, Team::Blue, 5, 2);
+
+        game.check_victory();
+        destroy(&mut game, flag);
+
+        assert_eq!(game.check_victory(), Outcome::Winner(Team::Red));
+        assert_eq!(game.units[tank].team, Team::Red);
+        assert_eq!(game.units[tank].actions, 0);
+    }
+
+    #[test]
+    fn holding_structures_for_enough_turns_wins() {
+        let mut game = game_with(vec![Box::new(HoldStructures::new(1, 2))]);
+        spawn(&mut game, UnitKind::BARRACKS, Team::Red,  1, 1);
+        spawn(&mut game, UnitKind::TANK,     Team::Blue, 5, 5);
+
+        // Red holds its Barracks at the start of turns 2 and 4.
+        assert!(game.next_turn());
+        assert!(game.next_turn());
+        assert!(game.next_turn());
+        assert!(!game.next_turn());
+
+        assert_eq!(game.turn, 4);
+        assert_eq!(game.outcome, Outcome::Winner(Team::Red));
+    }
+
+    #[test]
+    fn losing_the_structures_resets_the_count() {
+        let mut game = game_with(vec![Box::new(HoldStructures::new(1, 2))]);
+        let barracks = spawn(&mut game, UnitKind::BARRACKS, Team::Red,  1, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Red,  2, 2);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 5, 5);
+
+        assert!(game.next_turn());
+        assert!(game.next_turn());
+        destroy(&mut game, barracks);
+        assert!(game.next_turn());
+        assert!(game.next_turn());
+
+        assert_eq!(game.outcome, Outcome::Undecided);
+    }
+
+    #[test]
+    fn the_largest_army_wins_at_the_turn_limit() {
+        let mut game = game_with(vec![Box::new(TurnLimit::new(2))]);
+        spawn(&mut game, UnitKind::TANK, Team::Red,  1, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Red,  1, 2);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 5, 5);
+
+        assert!(game.next_turn());
+        assert!(game.next_turn());
+        assert!(!game.next_turn());
+
+        assert_eq!(game.outcome, Outcome::Winner(Team::Red));
+    }
+
+    #[test]
+    fn tied_armies_draw_at_the_turn_limit() {
+        let mut game = game_with(vec![Box::new(TurnLimit::new(1))]);
+        spawn(&mut game, UnitKind::TANK, Team::Red,  1, 1);
+        spawn(&mut game, UnitKind::TANK, Team::Blue, 5, 5);
+
+        assert!(game.next_turn());
+        assert!(!game.next_turn());
+
+        assert_eq!(game.outcome, Outcome::Draw);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{Dimension, Position, Metric};
+
+/// What one team can see of the board.
+///
+/// Tiles within sight of one of the team's units are visible. Tiles that
+/// have been visible at some point stay explored, so their terrain is
+/// remembered even though the units on them are not.
+#[derive(Debug, Clone)]
+pub struct Visibility {
+    size:     Dimension,
+    visible:  Vec<bool>,
+    explored: Vec<bool>
+}
+
+impl Visibility {
+    pub fn new(size: Dimension) -> Self {
+        Visibility {
+            size,
+            visible:  vec![false; size.area() as usize],
+            explored: vec![false; size.area() as usize]
+        }
+    }
+
+    pub fn is_visible(&self, position: Position) -> bool {
+        match self.to_index(position) {
+            Some(index) => self.visible[index],
+            None        => false
+        }
+    }
+
+    pub fn is_explored(&self, position: Position) -> bool {
+        match self.to_index(position) {
+            Some(index) => self.explored[index],
+            None        => false
+        }
+    }
+
+    /// Recomputes the visible tiles from the position and sight range of
+    /// each of the team's units, measuring sight with the metric.
+    pub fn update(&mut self, eyes: impl IntoIterator<Item = (Position, u32)>, metric: Metric) {
+        for visible in &mut self.visible {
+            *visible = false;
+        }
+
+        for (origin, sight) in eyes {
+            for position in origin.area(sight, metric) {
+                if let Some(index) = self.to_index(position) {
+                    self.visible[index]  = true;
+                    self.explored[index] = true;
+                }
+            }
+        }
+    }
+
+    /// Whether any of the positions is visible now but was not visible
+    /// before.
+    pub fn reveals_any(&self, before: &Visibility, positions: impl IntoIterator<Item = Position>) -> bool {
+        positions
+            .into_iter()
+            .any(|position| self.is_visible(position) && !before.is_visible(position))
+    }
+
+    /// Marks the tile as explored without it becoming visible, such as
+    /// when a saved game is loaded.
+    pub fn explore(&mut self, position: Position) {
+        if let Some(index) = self.to_index(position) {
+            self.explored[index] = true;
+        }
+    }
+
+    fn to_index(&self, position: Position) -> Option<usize> {
+        if position.x >= 0 && position.x < self.size.width  as i32 &&
+           position.y >= 0 && position.y < self.size.height as i32 {
+            Some((position.x + position.y * self.size.width as i32) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sight_reveals_a_diamond_and_explored_tiles_stay_explored() {
+        let mut visibility = Visibility::new(Dimension::new(9, 9));
+        visibility.update([(Position::new(4, 4), 2)], Metric::Manhattan);
+
+        assert!(visibility.is_visible(Position::new(4, 2)));
+        assert!(visibility.is_visible(Position::new(5, 5)));
+        assert!(!visibility.is_visible(Position::new(6, 5)));
+        assert!(!visibility.is_visible(Position::new(-1, 4)));
+
+        visibility.update([(Position::new(1, 1), 1)], Metric::Manhattan);
+
+        assert!(!visibility.is_visible(Position::new(4, 2)));
+        assert!(visibility.is_explored(Position::new(4, 2)));
+        assert!(!visibility.is_explored(Position::new(6, 5)));
+    }
+}